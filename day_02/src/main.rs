@@ -1,39 +1,28 @@
 use intcomputer::*;
-use std::fs;
 use std::sync::mpsc::channel;
 
-fn part1(digits: Vec<i64>) {
+fn part1(digits: Program) {
     let (_, input_r) = channel();
     let (output_s, _) = channel();
     let mut computer = SuperComputer::new("Computer".to_string(), digits, output_s, input_r);
-    computer.run();
+    computer.run().unwrap();
     println!("{}", computer.digits[0]);
 }
 
-fn part2(digits: Vec<i64>) {
-    for noun in 0..100 {
-        for verb in 0..100 {
-            let mut digits = digits.clone();
-            digits[1] = noun;
-            digits[2] = verb;
-            let (_, input_r) = channel();
-            let (output_s, _) = channel();
-            let mut computer =
-                SuperComputer::new("Computer".to_string(), digits, output_s, input_r);
-            computer.run();
-            if computer.digits[0] == 19690720 {
-                println!("{}", 100 * noun + verb);
-            }
-        }
+fn part2(digits: Program) {
+    // Solve for noun and verb rather than trying all 10000 pairs
+    let mut computer = SymbolicComputer::new(&digits);
+    computer.symbol(1, "noun");
+    computer.symbol(2, "verb");
+    computer.run().unwrap();
+    let result = computer.get(0).linear().expect("digits[0] isn't linear");
+    if let Some(solution) = result.solve(19690720, &[("noun", 0..100), ("verb", 0..100)]) {
+        println!("{}", 100 * solution[0].1 + solution[1].1);
     }
 }
 
 fn main() {
-    let digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+    let digits = Program::load("input.txt").unwrap();
     part1(digits.clone());
     part2(digits.clone());
 }
@@ -1,7 +1,6 @@
 use intcomputer::*;
 use itertools::Itertools;
 use std::collections::HashMap;
-use std::fs;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::SendError;
 use std::thread;
@@ -126,12 +125,7 @@ impl Robot {
 }
 
 fn main() -> Result<(), SendError<i64>> {
-    let digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+    let digits = Program::load("input.txt").unwrap();
 
     let mut robot = Robot::new(White);
     // PART 1
@@ -145,7 +139,7 @@ fn main() -> Result<(), SendError<i64>> {
             computer_output,
             computer_input,
         );
-        computer.run();
+        computer.run().unwrap();
     });
 
     loop {
@@ -1,6 +1,5 @@
 use intcomputer::*;
 use std::collections::HashMap;
-use std::fs;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::SendError;
 use std::thread;
@@ -34,12 +33,7 @@ impl Tile {
 }
 
 fn main() -> Result<(), SendError<i64>> {
-    let digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+    let digits = Program::load("input.txt").unwrap();
 
     // PART 1
     let (game_output, computer_input) = channel();
@@ -48,7 +42,7 @@ fn main() -> Result<(), SendError<i64>> {
         // Create a computer and run it
         let mut computer =
             SuperComputer::new("Gamer".to_string(), digits, computer_output, computer_input);
-        computer.run();
+        computer.run().unwrap();
     });
 
     let mut tiles = HashMap::new();
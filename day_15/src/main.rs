@@ -1,11 +1,6 @@
 use intcomputer::*;
 use priority_queue::PriorityQueue;
-use std::collections::HashMap;
-use std::fs;
-use std::sync::mpsc::channel;
-use std::sync::mpsc::{Receiver, SendError, Sender};
-
-use std::thread;
+use std::collections::{HashMap, VecDeque};
 
 const NORTH: i64 = 1;
 const SOUTH: i64 = 2;
@@ -49,15 +44,7 @@ impl Pos {
     }
 }
 
-fn opposite_dir(dir: i64) -> i64 {
-    match dir {
-        NORTH => SOUTH,
-        SOUTH => NORTH,
-        WEST => EAST,
-        EAST => WEST,
-        _ => panic!("impossible direction"),
-    }
-}
+type Robut = SuperComputer<VecDeque<i64>, Vec<i64>>;
 
 #[derive(Clone, Copy, Debug)]
 struct Node {
@@ -90,24 +77,26 @@ impl Node {
     }
 }
 
-// Checks each direction around a node, then DFS it with backtracking
+// Checks each direction around a node, then DFS it, restoring a snapshot to backtrack
 fn check_direction(
     dir: i64,
     node: &mut Node,
-    output: &Sender<i64>,
-    input: &Receiver<i64>,
+    robut: &mut Robut,
     nodes: &mut HashMap<Pos, Node>,
 ) {
     // position of node we are going to check
     let pos = node.pos.next_pos(dir);
-    // get the opposite dir so we can backtrack
-    let opposite_dir = opposite_dir(dir);
     match nodes.get(&pos) {
         None => {
+            // remember the robot in this room so we can come back
+            let here = robut.snapshot();
             // move the robot to the new room
-            output.send(dir).unwrap();
+            robut.provide_input(dir);
             // see what happened when we tried to move
-            let status = input.recv().unwrap();
+            let status = match robut.run_until_event().unwrap() {
+                Event::Output(status) => status,
+                event => panic!("robut did {:?} instead of moving", event),
+            };
             // If we moved, make a new node and recursively explore
             if status == MOVED || status == OXYGEN {
                 let is_oxygen = status == OXYGEN;
@@ -125,10 +114,9 @@ fn check_direction(
                 nodes.insert(node.pos, *node);
                 nodes.insert(pos, new_node);
                 // Recursion
-                explore_nodes(&mut new_node, output, input, nodes);
+                explore_nodes(&mut new_node, robut, nodes);
                 // Backtrack
-                output.send(opposite_dir).unwrap();
-                let _ = input.recv().unwrap();
+                robut.restore(&here);
             }
         }
         // Node already exists, just set the neighbor relationship
@@ -139,16 +127,11 @@ fn check_direction(
     }
 }
 
-fn explore_nodes(
-    node: &mut Node,
-    output: &Sender<i64>,
-    input: &Receiver<i64>,
-    nodes: &mut HashMap<Pos, Node>,
-) {
+fn explore_nodes(node: &mut Node, robut: &mut Robut, nodes: &mut HashMap<Pos, Node>) {
     node.explored = true;
     nodes.insert(node.pos, *node);
     for dir in 1 .. 5 {
-        check_direction(dir, node, output, input, nodes);
+        check_direction(dir, node, robut, nodes);
     }
 }
 
@@ -219,22 +202,10 @@ fn imbue_precious_oxygen(nodes: &mut HashMap<Pos, Node>) -> usize {
     count
 }
 
-fn main() -> Result<(), SendError<i64>> {
-    let digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+fn main() {
+    let digits = Program::load("input.txt").unwrap();
 
-    let (robut_output, computer_input) = channel();
-    let (computer_output, robut_input) = channel();
-    thread::spawn(move || {
-        // Create a computer and run it
-        let mut computer =
-            SuperComputer::new("Robut".to_string(), digits, computer_output, computer_input);
-        computer.run();
-    });
+    let mut robut = SuperComputer::standalone("Robut".to_string(), digits);
 
     let mut nodes = HashMap::new();
     let mut start_node = Node {
@@ -247,7 +218,7 @@ fn main() -> Result<(), SendError<i64>> {
         w: None,
     };
     nodes.insert(start_node.pos, start_node);
-    explore_nodes(&mut start_node, &robut_output, &robut_input, &mut nodes);
+    explore_nodes(&mut start_node, &mut robut, &mut nodes);
 
     let oxygen_node = nodes.values().filter(|node| node.is_oxygen).nth(0).unwrap();
 
@@ -257,6 +228,4 @@ fn main() -> Result<(), SendError<i64>> {
     // PART 2
     let count = imbue_precious_oxygen(&mut nodes);
     println!("time to imbue precious oxygen everywhere:{}", count);
-
-    Ok(())
 }
@@ -1,5 +1,4 @@
 use intcomputer::*;
-use std::fs;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::{Receiver, SendError, Sender};
 use std::thread;
@@ -83,12 +82,7 @@ fn is_intersection(x: usize, y: usize, map: &Vec<Vec<Tile>>) -> bool {
 }
 
 fn part1() {
-    let digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+    let digits = Program::load("input.txt").unwrap();
 
     let (_, computer_input) = channel();
     let (computer_output, camera_input) = channel();
@@ -97,7 +91,7 @@ fn part1() {
         // Create a computer and run it
         let mut computer =
             SuperComputer::new("Robut".to_string(), digits, computer_output, computer_input);
-        computer.run();
+        computer.run().unwrap();
     });
 
     let mut map = Vec::new();
@@ -139,14 +133,8 @@ fn part1() {
 }
 
 fn part2() {
-    let mut digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
-
-    digits[0] = 2;
+    // Wake the robot up
+    let digits = Program::load("input.txt").unwrap().patched(&[(0, 2)]);
 
     let (robot_output, computer_input) = channel();
     let (computer_output, robot_input) = channel();
@@ -157,7 +145,7 @@ fn part2() {
         // Create a computer and run it
         let mut computer =
             SuperComputer::new("Robut".to_string(), digits, computer_output, computer_input);
-        computer.run();
+        computer.run().unwrap();
     });
 
     // This program arrived at using a neural network made of meat
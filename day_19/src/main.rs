@@ -1,8 +1,4 @@
 use intcomputer::*;
-use std::fs;
-use std::sync::mpsc::channel;
-use std::sync::mpsc::{Receiver, SendError, Sender};
-use std::thread;
 
 fn will_it_fit(map: Vec<Vec<u8>>,w:usize,h:usize, square_size:usize) -> Option<(usize,usize)> {
     for map_y in 0.. h-square_size {
@@ -28,43 +24,20 @@ fn will_it_fit(map: Vec<Vec<u8>>,w:usize,h:usize, square_size:usize) -> Option<(
 }
 
 fn part1() {
-    let mut digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
-
-    let (drone_output, computer_input) = channel();
-    let (computer_output, drone_input) = channel();
-
-    let term = Term::new(drone_output, drone_input);
-
-    thread::spawn(move || {
-        // Create a computer and run it
-        let dclone = digits.clone();
-        let mut computer =
-            SuperComputer::new("Drone".to_string(), digits, computer_output, computer_input);
-        loop {
-            computer.run();
-            computer.reset(&dclone);
-        }
-    });
+    let digits = Program::load("input.txt").unwrap();
 
     let w :usize = 2000;
     let h :usize = 2000;
 
+    // Ask the drone about every point, spread over all the cores
+    let points = (0..h).flat_map(|y| (0..w).map(move |x| vec![x as i64, y as i64]));
+    let results = batch::evaluate(&digits, points).unwrap();
+
     let mut map : Vec<Vec<u8>> = Vec::new();
     let mut count = 0;
-    for y in 0 .. h {
-        map.push(Vec::new());
-        for x in 0 .. w {
-            term.send(x as i64);
-            term.send(y as i64);
-            let result = term.recv();
-            map[y as usize].push(result as u8);
-            count += result;
-        }
+    for row in results.chunks(w) {
+        map.push(row.iter().map(|output| output[0] as u8).collect());
+        count += row.iter().map(|output| output[0]).sum::<i64>();
     }
 
     println!("count:{}",count);
@@ -1,5 +1,4 @@
 use intcomputer::*;
-use std::fs;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::{Receiver, SendError, Sender};
 use std::thread;
@@ -7,12 +6,7 @@ use std::thread;
 
 
 fn part1() {
-    let mut digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+    let digits = Program::load("input.txt").unwrap();
 
     let (drone_output, computer_input) = channel();
     let (computer_output, drone_input) = channel();
@@ -24,7 +18,7 @@ fn part1() {
         let mut computer =
             SuperComputer::new("Drone".to_string(), digits, computer_output, computer_input);
 
-        computer.run();
+        computer.run().unwrap();
 
     });
 
@@ -44,12 +38,7 @@ fn part1() {
 
 
 fn part2() {
-    let mut digits: Vec<i64> = fs::read_to_string("input.txt")
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
+    let digits = Program::load("input.txt").unwrap();
 
     let (drone_output, computer_input) = channel();
     let (computer_output, drone_input) = channel();
@@ -61,7 +50,7 @@ fn part2() {
         let mut computer =
             SuperComputer::new("Drone".to_string(), digits, computer_output, computer_input);
 
-        computer.run();
+        computer.run().unwrap();
 
     });
 
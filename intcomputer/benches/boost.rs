@@ -0,0 +1,139 @@
+//! Times day 9's BOOST program in sensor boost mode, a few hundred thousand
+//! instructions of mostly recursion, with the decode cache on and off. Both are
+//! compared against a plain interpreter, the original run loop without paging, the
+//! instruction set registry or fault checks, so the ratios mean something on any
+//! machine. Run with `cargo bench`.
+
+use intcomputer::{Program, SuperComputer};
+use std::time::{Duration, Instant};
+
+const BOOST: &str = include_str!("../../day_09/input.txt");
+const RUNS: u32 = 200;
+
+// Decodes every instruction every time from a flat Vec, no paging, no registry and
+// no checks past what valid programs need. Returns the outputs and the instruction
+// count.
+fn plain(program: &[i64], mut input: Vec<i64>) -> (Vec<i64>, u64) {
+    let mut memory = program.to_vec();
+    let (mut sp, mut rb, mut instructions) = (0, 0, 0);
+    let mut output = Vec::new();
+    loop {
+        let instruction = memory[sp];
+        let address = |memory: &mut Vec<i64>, i: usize| {
+            let value = memory[sp + i];
+            let address = match instruction / [0, 100, 1000, 10000][i] % 10 {
+                0 => value as usize,
+                1 => return sp + i,
+                _ => (rb + value) as usize,
+            };
+            if address >= memory.len() {
+                memory.resize(address + 1, 0);
+            }
+            address
+        };
+        let (a, b, c) = match instruction % 100 {
+            99 => return (output, instructions),
+            1 | 2 | 7 | 8 => (
+                address(&mut memory, 1),
+                address(&mut memory, 2),
+                address(&mut memory, 3),
+            ),
+            5 | 6 => (address(&mut memory, 1), address(&mut memory, 2), 0),
+            _ => (address(&mut memory, 1), 0, 0),
+        };
+        instructions += 1;
+        sp = match instruction % 100 {
+            1 => {
+                memory[c] = memory[a] + memory[b];
+                sp + 4
+            }
+            2 => {
+                memory[c] = memory[a] * memory[b];
+                sp + 4
+            }
+            3 => {
+                memory[a] = input.remove(0);
+                sp + 2
+            }
+            4 => {
+                output.push(memory[a]);
+                sp + 2
+            }
+            5 if memory[a] != 0 => memory[b] as usize,
+            6 if memory[a] == 0 => memory[b] as usize,
+            5 | 6 => sp + 3,
+            7 => {
+                memory[c] = (memory[a] < memory[b]) as i64;
+                sp + 4
+            }
+            8 => {
+                memory[c] = (memory[a] == memory[b]) as i64;
+                sp + 4
+            }
+            9 => {
+                rb += memory[a];
+                sp + 2
+            }
+            code => panic!("unknown op code {}", code),
+        };
+    }
+}
+
+// Best and mean time of a run, and how many instructions a run is. None runs the
+// plain interpreter, otherwise a SuperComputer with the decode cache on or off.
+fn time(program: &Program, cached: Option<bool>) -> (Duration, Duration, u64) {
+    let mut best = Duration::from_secs(u64::MAX);
+    let mut total = Duration::default();
+    let mut instructions = 0;
+    for _ in 0..RUNS {
+        let (elapsed, output) = match cached {
+            None => {
+                let start = Instant::now();
+                let (output, count) = plain(program, vec![2]);
+                instructions = count;
+                (start.elapsed(), output)
+            }
+            Some(cached) => {
+                let mut computer = SuperComputer::standalone("BOOST".to_string(), program.clone());
+                computer.set_decode_cache(cached);
+                computer.provide_input(2);
+                let start = Instant::now();
+                computer.run().unwrap();
+                let elapsed = start.elapsed();
+                instructions = computer.instructions();
+                (elapsed, computer.output)
+            }
+        };
+        assert_eq!(output.len(), 1);
+        best = best.min(elapsed);
+        total += elapsed;
+    }
+    (best, total / RUNS, instructions)
+}
+
+fn main() {
+    let program = Program::parse(BOOST).unwrap();
+
+    // Warm up before anything is timed
+    time(&program, Some(true));
+    let (plain, _, instructions) = time(&program, None);
+    println!(
+        "boost part 2, plain    : {} instructions, best {:?} ({:.1} M instructions/s)",
+        instructions,
+        plain,
+        instructions as f64 / plain.as_secs_f64() / 1e6
+    );
+    for &cached in &[false, true] {
+        let (best, mean, instructions) = time(&program, Some(cached));
+        println!(
+            "boost part 2, cache {}: {} instructions, best {:?}, mean {:?} over {} runs ({:.1} M instructions/s, {:.2}x plain)",
+            if cached { "on " } else { "off" },
+            instructions,
+            best,
+            mean,
+            RUNS,
+            instructions as f64 / best.as_secs_f64() / 1e6,
+            plain.as_secs_f64() / best.as_secs_f64()
+        );
+    }
+}
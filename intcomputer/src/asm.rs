@@ -0,0 +1,607 @@
+//! Assemble Intcode from text, in the same syntax `disasm` prints.
+//!
+//! ```text
+//! ; comments run to the end of the line
+//!         IN   [count]          ; [addr] is position mode
+//! loop:   OUT  #72              ; #value is immediate mode
+//!         ARB  #-1
+//!         ADD  rb+1, #0, rb+0   ; rb+n / rb-n is relative mode
+//!         ADD  [count], #-1, [count]
+//!         JT   [count], #loop   ; labels can be used anywhere a number can, eg #loop+2
+//!         HLT
+//! count:  db   0
+//! hello:  db   "Hello\n", 0     ; data directive, strings become one cell per char
+//! ```
+//!
+//! A numeric label like `0012:` is the address column of a listing, it is checked
+//! against the address being assembled rather than defined.
+
+use crate::{OpCode, ParameterMode};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    // Just the digits, a minus is its own token so this has to hold i64::MIN's magnitude
+    Number(u64),
+    Str(String),
+    Colon,
+    Comma,
+    Hash,
+    Open,
+    Close,
+    Plus,
+    Minus,
+    End,
+}
+
+// Tokens of one line, with their 1 based column
+struct Lexer<'a> {
+    line: usize,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    fn error(&self, column: usize, message: String) -> AsmError {
+        AsmError {
+            line: self.line,
+            column: column + 1,
+            message,
+        }
+    }
+
+    fn tokens(mut self) -> Result<Vec<(Token, usize)>, AsmError> {
+        let mut tokens = Vec::new();
+        while let Some((column, c)) = self.chars.next() {
+            let token = match c {
+                ';' => break,
+                c if c.is_whitespace() => continue,
+                ':' => Token::Colon,
+                ',' => Token::Comma,
+                '#' => Token::Hash,
+                '[' => Token::Open,
+                ']' => Token::Close,
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '"' => Token::Str(self.string(column)?),
+                c if c.is_ascii_digit() => {
+                    let mut digits = c.to_string();
+                    while let Some((_, c)) = self.chars.peek().filter(|(_, c)| c.is_ascii_digit()) {
+                        digits.push(*c);
+                        self.chars.next();
+                    }
+                    match digits.parse() {
+                        Ok(n) => Token::Number(n),
+                        Err(_) => {
+                            return Err(self.error(column, format!("number {} too large", digits)))
+                        }
+                    }
+                }
+                c if c.is_alphabetic() || c == '_' || c == '.' => {
+                    let mut name = c.to_string();
+                    while let Some((_, c)) = self
+                        .chars
+                        .peek()
+                        .filter(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '.')
+                    {
+                        name.push(*c);
+                        self.chars.next();
+                    }
+                    Token::Ident(name)
+                }
+                c => return Err(self.error(column, format!("unexpected character {:?}", c))),
+            };
+            tokens.push((token, column + 1));
+        }
+        tokens.push((Token::End, 0));
+        Ok(tokens)
+    }
+
+    fn string(&mut self, start: usize) -> Result<String, AsmError> {
+        let mut result = String::new();
+        loop {
+            let (column, c) = match self.chars.next() {
+                Some(next) => next,
+                None => return Err(self.error(start, "unterminated string".to_string())),
+            };
+            match c {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escaped = match self.chars.next() {
+                        Some((_, 'n')) => '\n',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'r')) => '\r',
+                        Some((_, '0')) => '\0',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '"')) => '"',
+                        Some((_, '\'')) => '\'',
+                        Some((_, 'u')) => self.unicode(column)?,
+                        _ => return Err(self.error(column, "unknown escape".to_string())),
+                    };
+                    result.push(escaped);
+                }
+                c => result.push(c),
+            }
+        }
+    }
+
+    // The {XX} of a \u{XX} escape
+    fn unicode(&mut self, column: usize) -> Result<char, AsmError> {
+        let mut hex = String::new();
+        if self.chars.next().map(|(_, c)| c) != Some('{') {
+            return Err(self.error(column, "expected { after \\u".to_string()));
+        }
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((_, c)) => hex.push(c),
+                None => return Err(self.error(column, "unterminated \\u escape".to_string())),
+            }
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| self.error(column, format!("invalid \\u escape {}", hex)))
+    }
+}
+
+// A number or label, plus or minus more of them
+#[derive(Debug, Clone)]
+struct Expr {
+    terms: Vec<(i64, Term)>,
+}
+
+#[derive(Debug, Clone)]
+// Both with the column they start at
+enum Term {
+    Number(i64, usize),
+    Label(String, usize),
+}
+
+#[derive(Debug)]
+enum Item {
+    Op(OpCode, Vec<(ParameterMode, Expr)>),
+    Data(Vec<Expr>),
+}
+
+struct Parser {
+    line: usize,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn column(&self) -> usize {
+        match self.tokens[self.pos] {
+            (Token::End, _) if self.pos > 0 => self.tokens[self.pos - 1].1,
+            (_, column) => column,
+        }
+    }
+
+    fn error(&self, message: String) -> AsmError {
+        AsmError {
+            line: self.line,
+            column: self.column(),
+            message,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), AsmError> {
+        if *self.peek() == expected {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {:?}, found {:?}", expected, self.peek())))
+        }
+    }
+
+    // Labels defined at the start of the line, numeric ones are listing addresses
+    fn labels(&mut self) -> Vec<(Result<String, u64>, usize)> {
+        let mut labels = Vec::new();
+        while self.tokens.len() > self.pos + 1 && self.tokens[self.pos + 1].0 == Token::Colon {
+            let column = self.column();
+            match self.next() {
+                Token::Ident(name) => labels.push((Ok(name), column)),
+                Token::Number(address) => labels.push((Err(address), column)),
+                _ => {
+                    self.pos -= 1;
+                    break;
+                }
+            }
+            self.next();
+        }
+        labels
+    }
+
+    // A number or label, negated for each minus in front of it and `sign`. Numbers are
+    // range checked once their sign is known and come back with a sign of 1.
+    fn term(&mut self, mut sign: i64) -> Result<(i64, Term), AsmError> {
+        let start = self.column();
+        while *self.peek() == Token::Minus {
+            self.next();
+            sign = -sign;
+        }
+        let column = self.column();
+        match self.next() {
+            Token::Number(n) => match i64::try_from(i128::from(n) * i128::from(sign)) {
+                Ok(value) => Ok((1, Term::Number(value, start))),
+                Err(_) => Err(AsmError {
+                    line: self.line,
+                    column: start,
+                    message: format!(
+                        "number {}{} out of range",
+                        if sign < 0 { "-" } else { "" },
+                        n
+                    ),
+                }),
+            },
+            Token::Ident(name) => Ok((sign, Term::Label(name, column))),
+            token => {
+                self.pos -= 1;
+                Err(self.error(format!("expected a number or label, found {:?}", token)))
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, AsmError> {
+        let mut terms = vec![self.term(1)?];
+        loop {
+            let sign = match self.peek() {
+                Token::Plus => 1,
+                Token::Minus => -1,
+                _ => return Ok(Expr { terms }),
+            };
+            self.next();
+            terms.push(self.term(sign)?);
+        }
+    }
+
+    fn operand(&mut self) -> Result<(ParameterMode, Expr), AsmError> {
+        match self.peek().clone() {
+            Token::Hash => {
+                self.next();
+                Ok((ParameterMode::Value, self.expr()?))
+            }
+            Token::Open => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect(Token::Close)?;
+                Ok((ParameterMode::Pointer, expr))
+            }
+            Token::Ident(ref name) if name.eq_ignore_ascii_case("rb") => {
+                let column = self.column();
+                self.next();
+                match self.peek() {
+                    Token::Plus | Token::Minus => {
+                        let mut expr = Expr {
+                            terms: vec![(1, Term::Number(0, column))],
+                        };
+                        while let Token::Plus | Token::Minus = self.peek() {
+                            let sign = if self.next() == Token::Plus { 1 } else { -1 };
+                            expr.terms.push(self.term(sign)?);
+                        }
+                        Ok((ParameterMode::Relative, expr))
+                    }
+                    _ => Ok((
+                        ParameterMode::Relative,
+                        Expr {
+                            terms: vec![(1, Term::Number(0, column))],
+                        },
+                    )),
+                }
+            }
+            token => Err(self.error(format!("expected #, [ or rb, found {:?}", token))),
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, AsmError>,
+    ) -> Result<Vec<T>, AsmError> {
+        let mut result = Vec::new();
+        if *self.peek() == Token::End {
+            return Ok(result);
+        }
+        result.push(item(self)?);
+        while *self.peek() == Token::Comma {
+            self.next();
+            result.push(item(self)?);
+        }
+        Ok(result)
+    }
+
+    fn item(&mut self) -> Result<Option<Item>, AsmError> {
+        let column = self.column();
+        let name = match self.next() {
+            Token::End => return Ok(None),
+            Token::Ident(name) => name,
+            token => {
+                self.pos -= 1;
+                return Err(self.error(format!("expected an instruction, found {:?}", token)));
+            }
+        };
+        let item = if name.eq_ignore_ascii_case("db") {
+            let mut values = Vec::new();
+            for exprs in self.list(|parser| match parser.peek().clone() {
+                Token::Str(text) => {
+                    let column = parser.column();
+                    parser.next();
+                    Ok(text
+                        .chars()
+                        .map(|c| Expr {
+                            terms: vec![(1, Term::Number(c as i64, column))],
+                        })
+                        .collect())
+                }
+                _ => Ok(vec![parser.expr()?]),
+            })? {
+                values.extend(exprs);
+            }
+            Item::Data(values)
+        } else {
+            let op_code = OpCode::ALL
+                .iter()
+                .copied()
+                .find(|op_code| op_code.mnemonic().eq_ignore_ascii_case(&name))
+                .ok_or_else(|| AsmError {
+                    line: self.line,
+                    column,
+                    message: format!("unknown instruction {}", name),
+                })?;
+            let operands = self.list(Parser::operand)?;
+            if operands.len() != op_code.num_params() {
+                return Err(AsmError {
+                    line: self.line,
+                    column,
+                    message: format!(
+                        "{} takes {} operands, found {}",
+                        op_code.mnemonic(),
+                        op_code.num_params(),
+                        operands.len()
+                    ),
+                });
+            }
+            if let Some(write) = op_code.write_param() {
+                if operands[write].0 == ParameterMode::Value {
+                    return Err(AsmError {
+                        line: self.line,
+                        column,
+                        message: format!(
+                            "{} can't write to an immediate operand",
+                            op_code.mnemonic()
+                        ),
+                    });
+                }
+            }
+            Item::Op(op_code, operands)
+        };
+        if *self.peek() != Token::End {
+            return Err(self.error(format!("unexpected {:?}", self.peek())));
+        }
+        Ok(Some(item))
+    }
+}
+
+fn resolve(expr: &Expr, line: usize, labels: &HashMap<String, usize>) -> Result<i64, AsmError> {
+    let mut value = 0i64;
+    for (sign, term) in &expr.terms {
+        let (term, column) = match term {
+            Term::Number(n, column) => (*n, *column),
+            Term::Label(name, column) => match labels.get(name) {
+                Some(address) => (*address as i64, *column),
+                None => {
+                    return Err(AsmError {
+                        line,
+                        column: *column,
+                        message: format!("undefined label {}", name),
+                    })
+                }
+            },
+        };
+        value = match term
+            .checked_mul(*sign)
+            .and_then(|term| value.checked_add(term))
+        {
+            Some(value) => value,
+            None => {
+                return Err(AsmError {
+                    line,
+                    column,
+                    message: "expression out of range".to_string(),
+                })
+            }
+        };
+    }
+    Ok(value)
+}
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    // First pass, parse every line and find where the labels land
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut address = 0;
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let lexer = Lexer {
+            line,
+            chars: text.char_indices().peekable(),
+        };
+        let mut parser = Parser {
+            line,
+            tokens: lexer.tokens()?,
+            pos: 0,
+        };
+        for (label, column) in parser.labels() {
+            match label {
+                Ok(name) => {
+                    if labels.insert(name.clone(), address).is_some() {
+                        return Err(AsmError {
+                            line,
+                            column,
+                            message: format!("label {} defined twice", name),
+                        });
+                    }
+                }
+                Err(expected) if expected != address as u64 => {
+                    return Err(AsmError {
+                        line,
+                        column,
+                        message: format!(
+                            "listing address {} but assembling at {}",
+                            expected, address
+                        ),
+                    })
+                }
+                Err(_) => (),
+            }
+        }
+        if let Some(item) = parser.item()? {
+            address += match &item {
+                Item::Op(_, operands) => operands.len() + 1,
+                Item::Data(values) => values.len(),
+            };
+            items.push((line, item));
+        }
+    }
+
+    // Second pass, now every label has an address
+    let mut program = Vec::with_capacity(address);
+    for (line, item) in items {
+        match item {
+            Item::Op(op_code, operands) => {
+                let mut instruction = op_code.code();
+                let mut scale = 100;
+                for (mode, _) in &operands {
+                    instruction += mode.digit() * scale;
+                    scale *= 10;
+                }
+                program.push(instruction);
+                for (_, expr) in &operands {
+                    program.push(resolve(expr, line, &labels)?);
+                }
+            }
+            Item::Data(values) => {
+                for expr in &values {
+                    program.push(resolve(expr, line, &labels)?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disassemble, Program};
+
+    macro_rules! day {
+        ($day:literal) => {
+            include_str!(concat!("../../day_", $day, "/input.txt"))
+        };
+    }
+
+    const DAYS: [&str; 10] = [
+        day!("02"),
+        day!("05"),
+        day!("07"),
+        day!("09"),
+        day!("11"),
+        day!("13"),
+        day!("15"),
+        day!("17"),
+        day!("19"),
+        day!("21"),
+    ];
+
+    #[test]
+    fn disassembled_days_assemble_back() {
+        for input in DAYS.iter() {
+            let program = Program::parse(input).unwrap();
+            let listing: Vec<String> = disassemble(&program)
+                .iter()
+                .map(|line| line.to_string())
+                .collect();
+            assert_eq!(assemble(&listing.join("\n")).unwrap(), &program[..]);
+        }
+    }
+
+    #[test]
+    fn operands_span_all_of_i64() {
+        let program = assemble(
+            "ADD #-9223372036854775808, #9223372036854775807, [0]\n\
+             MUL #-1, #- -9223372036854775807, rb-9223372036854775808\n\
+             db -9223372036854775808, 9223372036854775807",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            [
+                1101,
+                i64::MIN,
+                i64::MAX,
+                0,
+                21102,
+                -1,
+                i64::MAX,
+                i64::MIN,
+                i64::MIN,
+                i64::MAX
+            ]
+        );
+        let listing: Vec<String> = disassemble(&program[..8])
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(assemble(&listing.join("\n")).unwrap(), &program[..8]);
+    }
+
+    #[test]
+    fn numbers_past_i64_are_errors() {
+        for source in &["db 9223372036854775808", "db --9223372036854775808"] {
+            let error = assemble(source).unwrap_err();
+            assert_eq!((error.line, error.column), (1, 4));
+        }
+        assert!(assemble("db -18446744073709551616").is_err());
+
+        let error = assemble("db 9223372036854775807+1").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 24));
+        let error = assemble("end: db -9223372036854775808 - end - 1").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 38));
+        assert_eq!(
+            assemble("db 9223372036854775807+1-1").unwrap_err().column,
+            24
+        );
+        assert_eq!(assemble("db 1-9223372036854775807-2").unwrap(), [i64::MIN]);
+    }
+}
@@ -0,0 +1,115 @@
+//! Run one program over lots of inputs on every core.
+//!
+//! ```no_run
+//! # use intcomputer::{batch, IntcodeError, Program};
+//! # fn main() -> Result<(), IntcodeError> {
+//! # let program = Program::load("input.txt").unwrap();
+//! // Is each point in the tractor beam?
+//! let points = (0..50).flat_map(|y| (0..50).map(move |x| vec![x, y]));
+//! let beam = batch::evaluate(&program, points)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each worker thread keeps one machine and resets it between jobs. The machines share
+//! one copy of the program, and a reset only copies back the pages the last job wrote
+//! to.
+
+use crate::{IntcodeError, Machine, SuperComputer};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Run the program once for each list of inputs, returning the outputs of each run in
+/// the same order as the inputs
+pub fn evaluate<T>(program: &[i64], inputs: T) -> Result<Vec<Vec<i64>>, IntcodeError>
+where
+    T: IntoIterator<Item = Vec<i64>>,
+    T::IntoIter: Send,
+{
+    map(program, inputs, |machine, inputs| {
+        for input in inputs {
+            machine.provide_input(input);
+        }
+        machine.run()?;
+        Ok(std::mem::take(&mut machine.output))
+    })
+    .into_iter()
+    .collect()
+}
+
+/// Call `job` with a freshly reset machine for each item, returning the results in
+/// the same order as the items. The machine is standalone with nothing queued and no
+/// outputs, `job` can poke its memory, feed it and run it however it likes.
+pub fn map<T, R, F>(program: &[i64], items: T, job: F) -> Vec<R>
+where
+    T: IntoIterator,
+    T::IntoIter: Send,
+    T::Item: Send,
+    R: Send,
+    F: Fn(&mut Machine, T::Item) -> R + Sync,
+{
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let image: Arc<[i64]> = program.into();
+    let items = Mutex::new(items.into_iter().enumerate());
+
+    let mut done: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                let (items, image, job) = (&items, &image, &job);
+                scope.spawn(move || {
+                    let mut machine = SuperComputer::standalone(worker.to_string(), image.clone());
+                    let mut done = Vec::new();
+                    loop {
+                        // Only hold the lock long enough to take the next item
+                        let next = items.lock().unwrap().next();
+                        let (i, item) = match next {
+                            Some(next) => next,
+                            None => return done,
+                        };
+                        machine.reset();
+                        machine.input.clear();
+                        machine.output.clear();
+                        machine.last_output = None;
+                        done.push((i, job(&mut machine, item)));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    done.sort_unstable_by_key(|(i, _)| *i);
+    done.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn results_in_order_and_nothing_left_from_the_last_job() {
+        // Adds its first input to a total kept in memory. Only the first input is read,
+        // so a job's second input would be the next job's first if it were left queued.
+        let program =
+            assemble("IN [x]\nADD [x], [total], [total]\nOUT [total]\nHLT\nx: db 0\ntotal: db 0")
+                .unwrap();
+        let jobs = thread::available_parallelism().map_or(1, |n| n.get()) * 8 + 3;
+        let inputs = (0..jobs as i64).map(|i| vec![i, 1000 + i]);
+        let expected: Vec<_> = (0..jobs as i64).map(|i| vec![i]).collect();
+        assert_eq!(evaluate(&program, inputs), Ok(expected));
+
+        // Poking memory doesn't carry over either
+        let totals = map(&program, 0..jobs as i64, |machine, i| {
+            let before = machine.digits.get(10);
+            machine.digits.set(10, i);
+            machine.provide_input(1);
+            machine.run().unwrap();
+            (before, machine.last_output)
+        });
+        let expected: Vec<_> = (0..jobs as i64).map(|i| (0, Some(i + 1))).collect();
+        assert_eq!(totals, expected);
+    }
+}
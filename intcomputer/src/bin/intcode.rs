@@ -0,0 +1,261 @@
+use intcomputer::*;
+use std::env;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "\
+usage: intcode <command> <program> [options]
+
+commands:
+  run <program>       run with numbers as input and output, one per line
+  ascii <program>     run with text input and output on stdin and stdout
+  disasm <program>    print a listing of the program
+  cfg <program>       print the control flow graph in Graphviz DOT
+  trace <program>     run, printing every instruction executed
+  selfmod <program>   run like run, then report writes to executed instructions
+  convert <program>   write the program out in another format, needs --output
+
+Programs can be text or binary images, whichever they are.
+
+options:
+  --input <values>    comma separated numbers queued before anything from stdin
+  --patch <addr=val>  change memory before running, can be repeated or comma separated
+  --output <path>     where convert writes to
+  --format <format>   what convert writes, text, binary or compact (default)";
+
+struct Options {
+    command: String,
+    path: String,
+    input: Vec<i64>,
+    patches: Vec<(usize, i64)>,
+    output: Option<String>,
+    format: Format,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn parse_numbers(text: &str) -> Result<Vec<i64>, String> {
+    text.split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("{} is not a number", s.trim()))
+        })
+        .collect()
+}
+
+fn parse_patches(text: &str) -> Result<Vec<(usize, i64)>, String> {
+    text.split(',')
+        .map(|patch| {
+            let mut parts = patch.splitn(2, '=');
+            let address = parts.next().unwrap().trim().parse::<usize>();
+            let value = parts.next().map(|value| value.trim().parse::<i64>());
+            match (address, value) {
+                (Ok(address), Some(Ok(value))) => Ok((address, value)),
+                _ => Err(format!("expected address=value, got {}", patch)),
+            }
+        })
+        .collect()
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let command = args.next().ok_or("no command")?.clone();
+    let mut path = None;
+    let mut input = Vec::new();
+    let mut patches = Vec::new();
+    let mut output = None;
+    let mut format = Format::Compact;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                let values = args.next().ok_or("--input needs values")?;
+                input.extend(parse_numbers(values)?);
+            }
+            "--patch" => {
+                let values = args.next().ok_or("--patch needs address=value")?;
+                patches.extend(parse_patches(values)?);
+            }
+            "--output" => output = Some(args.next().ok_or("--output needs a path")?.clone()),
+            "--format" => {
+                format = match args.next().map(String::as_str) {
+                    Some("text") => Format::Text,
+                    Some("binary") => Format::Binary,
+                    Some("compact") => Format::Compact,
+                    _ => return Err("--format needs text, binary or compact".to_string()),
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    Ok(Options {
+        command,
+        path: path.ok_or("no program given")?,
+        input,
+        patches,
+        output,
+        format,
+    })
+}
+
+fn load(options: &Options) -> Program {
+    Program::load(&options.path)
+        .map(|program| program.patched(&options.patches))
+        .unwrap_or_else(|err| fail(&format!("could not load {}: {}", options.path, err)))
+}
+
+// Numbers typed on stdin, separated by commas or whitespace
+fn stdin_numbers() -> impl FnMut() -> Option<i64> {
+    let stdin = io::stdin();
+    let mut pending = Vec::new();
+    move || loop {
+        if let Some(value) = pending.pop() {
+            return Some(value);
+        }
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        for word in line.split(|c: char| c == ',' || c.is_whitespace()) {
+            match word.parse::<i64>() {
+                Ok(value) => pending.insert(0, value),
+                Err(_) if word.is_empty() => (),
+                Err(_) => eprintln!("ignoring {}, not a number", word),
+            }
+        }
+    }
+}
+
+// Lines typed on stdin as ASCII codes, newlines included
+fn stdin_text() -> impl FnMut() -> Option<i64> {
+    let stdin = io::stdin();
+    let mut pending = Vec::new();
+    move || {
+        if pending.is_empty() {
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            pending = line.bytes().rev().map(i64::from).collect();
+        }
+        pending.pop()
+    }
+}
+
+// The disassembly with symbols as labels on their own lines
+fn listing(program: &Program) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for instruction in disasm::disassemble_program(program) {
+        for label in program.symbols_at(instruction.address()) {
+            writeln!(stdout, "{}:", label)?;
+        }
+        writeln!(stdout, "{}", instruction)?;
+    }
+    Ok(())
+}
+
+fn finish(name: &str, result: Result<HaltReason, IntcodeError>) {
+    match result {
+        Ok(HaltReason::Halted) => (),
+        Ok(reason) => eprintln!("{} stopped: {:?}", name, reason),
+        Err(err) => fail(&format!("{} faulted: {}", name, err)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args[0] == "help" || args[0] == "--help" {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = parse_args(&args).unwrap_or_else(|err| fail(&format!("{}\n\n{}", err, USAGE)));
+    let digits = load(&options);
+    let name = options.path.clone();
+
+    match options.command.as_str() {
+        "run" => {
+            let output = FnOutput(|value: i64| {
+                println!("{}", value);
+                true
+            });
+            let mut computer =
+                SuperComputer::new(name.clone(), digits, output, FnInput(stdin_numbers()));
+            for value in options.input {
+                computer.provide_input(value);
+            }
+            finish(&name, computer.run());
+        }
+        "ascii" => {
+            // Anything that isn't ASCII, like a final answer, goes on its own line
+            let output = FnOutput(|value: i64| {
+                if (0..128).contains(&value) {
+                    print!("{}", value as u8 as char);
+                    if value == 10 {
+                        io::stdout().flush().unwrap();
+                    }
+                } else {
+                    println!("{}", value);
+                }
+                true
+            });
+            let mut computer =
+                SuperComputer::new(name.clone(), digits, output, FnInput(stdin_text()));
+            for value in options.input {
+                computer.provide_input(value);
+            }
+            let result = computer.run();
+            io::stdout().flush().unwrap();
+            finish(&name, result);
+        }
+        "selfmod" => {
+            let output = FnOutput(|value: i64| {
+                println!("{}", value);
+                true
+            });
+            let mut computer =
+                SuperComputer::new(name.clone(), digits, output, FnInput(stdin_numbers()));
+            for value in options.input {
+                computer.provide_input(value);
+            }
+            let mut watcher = CodeWatcher::new();
+            let result = computer.run_with(&mut watcher);
+            print!("{}", watcher);
+            finish(&name, result);
+        }
+        "trace" => {
+            // The trace shows every output, so they aren't printed again
+            let output = FnOutput(|_| true);
+            let mut computer =
+                SuperComputer::new(name.clone(), digits, output, FnInput(stdin_numbers()));
+            for value in options.input {
+                computer.provide_input(value);
+            }
+            let mut tracer = Tracer::new(BufWriter::new(io::stdout()));
+            let result = computer.run_with(&mut tracer);
+            if let Err(err) = tracer.into_inner().and_then(|mut writer| writer.flush()) {
+                fail(&format!("could not write trace: {}", err));
+            }
+            finish(&name, result);
+        }
+        "disasm" => {
+            // Stop quietly if stdout closes, eg piped into head
+            let _ = listing(&digits);
+        }
+        "cfg" => print!("{}", cfg::build_program(&digits).to_dot()),
+        "convert" => {
+            let path = options
+                .output
+                .unwrap_or_else(|| fail("convert needs --output <path>"));
+            if let Err(err) = digits.save(&path, options.format) {
+                fail(&format!("could not write {}: {}", path, err));
+            }
+        }
+        command => fail(&format!("unknown command {}\n\n{}", command, USAGE)),
+    }
+}
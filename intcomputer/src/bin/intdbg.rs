@@ -0,0 +1,213 @@
+use intcomputer::disasm::{decode, Instruction};
+use intcomputer::*;
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands:
+  s, step [n]         execute n instructions (default 1)
+  c, continue         run until a breakpoint, watchpoint, halt or input runs out
+  b, break <addr>     set a breakpoint
+  d, delete <addr>    remove a breakpoint or watchpoint
+  w, watch <addr>     stop when <addr> is written
+  r, regs             show sp and rb
+  m, mem <addr> [n]   show n cells of memory (default 8)
+  l, list [addr] [n]  disassemble n instructions (default sp, 8)
+  i, input <values>   queue input, comma separated numbers or a \"string\"
+  q, quit";
+
+// The instruction at address, decoded from memory, or a single data cell
+fn instruction_at<I, O>(debugger: &Debugger<I, O>, address: usize) -> Instruction
+where
+    I: IntcodeInput,
+    O: IntcodeOutput,
+{
+    let cells = debugger.memory(address, 4);
+    match decode(&cells, 0) {
+        Some(Instruction::Op {
+            op_code, operands, ..
+        }) => Instruction::Op {
+            address,
+            op_code,
+            operands,
+        },
+        _ => Instruction::Data {
+            address,
+            values: vec![cells[0]],
+        },
+    }
+}
+
+// Whether start..start + len is all below the memory limit
+fn in_memory<I, O>(debugger: &Debugger<I, O>, start: usize, len: usize) -> bool {
+    start
+        .checked_add(len)
+        .is_some_and(|end| end <= debugger.computer.digits.limit())
+}
+
+fn parse_input(args: &str) -> Option<Vec<i64>> {
+    let args = args.trim();
+    if args.starts_with('"') && args.ends_with('"') && args.len() >= 2 {
+        let text = args[1..args.len() - 1].replace("\\n", "\n");
+        return Some(text.chars().map(|c| c as i64).collect());
+    }
+    args.split(',')
+        .map(|s| s.trim().parse::<i64>().ok())
+        .collect()
+}
+
+fn main() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "input.txt".to_string());
+    let digits = Program::load(&path).unwrap_or_else(|err| {
+        eprintln!("could not load {}: {}", path, err);
+        std::process::exit(1);
+    });
+
+    // Show ASCII output as text and anything else as a number on its own line
+    let output = FnOutput(|value: i64| {
+        if (0..128).contains(&value) {
+            print!("{}", value as u8 as char);
+        } else {
+            println!("<{}>", value);
+        }
+        true
+    });
+    let computer = SuperComputer::new(path.clone(), digits, output, VecDeque::new());
+    let mut debugger = Debugger::new(computer);
+
+    println!("loaded {}, type help for commands", path);
+    let stdin = io::stdin();
+    loop {
+        print!("({}) ", debugger.sp());
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let line = line.trim();
+        let (command, args) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        let numbers: Vec<usize> = args
+            .split_whitespace()
+            .filter_map(|arg| arg.parse().ok())
+            .collect();
+
+        let result = match command {
+            "" => continue,
+            "h" | "help" => {
+                println!("{}", HELP);
+                continue;
+            }
+            "q" | "quit" => break,
+            "s" | "step" => {
+                let mut result = Ok(Stop::Stepped);
+                for _ in 0..numbers.first().copied().unwrap_or(1) {
+                    result = debugger.step();
+                    match result {
+                        Ok(Stop::Stepped) => (),
+                        _ => break,
+                    }
+                }
+                result
+            }
+            "c" | "continue" => debugger.cont(),
+            "b" | "break" => {
+                for address in numbers {
+                    debugger.add_breakpoint(address);
+                }
+                println!(
+                    "breakpoints: {:?}",
+                    debugger.breakpoints().collect::<Vec<_>>()
+                );
+                continue;
+            }
+            "w" | "watch" => {
+                for address in numbers {
+                    debugger.add_watchpoint(address);
+                }
+                println!(
+                    "watchpoints: {:?}",
+                    debugger.watchpoints().collect::<Vec<_>>()
+                );
+                continue;
+            }
+            "d" | "delete" => {
+                for address in numbers {
+                    if !debugger.remove_breakpoint(address) && !debugger.remove_watchpoint(address)
+                    {
+                        println!("nothing set at {}", address);
+                    }
+                }
+                continue;
+            }
+            "r" | "regs" => {
+                println!("sp {} rb {}", debugger.sp(), debugger.rb());
+                continue;
+            }
+            "m" | "mem" => {
+                let start = numbers.first().copied().unwrap_or_else(|| debugger.sp());
+                let len = numbers.get(1).copied().unwrap_or(8);
+                if !in_memory(&debugger, start, len) {
+                    println!(
+                        "{} cells from {} is past the memory limit of {}",
+                        len,
+                        start,
+                        debugger.computer.digits.limit()
+                    );
+                    continue;
+                }
+                for (i, chunk) in debugger.memory(start, len).chunks(8).enumerate() {
+                    println!("{:04}: {:?}", start + i * 8, chunk);
+                }
+                continue;
+            }
+            "l" | "list" => {
+                let mut address = numbers.first().copied().unwrap_or_else(|| debugger.sp());
+                for _ in 0..numbers.get(1).copied().unwrap_or(8) {
+                    if !in_memory(&debugger, address, 1) {
+                        println!("{} is past the memory limit", address);
+                        break;
+                    }
+                    let instruction = instruction_at(&debugger, address);
+                    let marker = if address == debugger.sp() { ">" } else { " " };
+                    println!("{} {}", marker, instruction);
+                    address = match address.checked_add(instruction.len()) {
+                        Some(next) => next,
+                        None => break,
+                    };
+                }
+                continue;
+            }
+            "i" | "input" => {
+                match parse_input(args) {
+                    Some(values) => debugger.computer.input.extend(values),
+                    None => println!("expected comma separated numbers or a \"string\""),
+                }
+                continue;
+            }
+            _ => {
+                println!("unknown command {}, type help for commands", command);
+                continue;
+            }
+        };
+
+        match result {
+            Ok(Stop::Stepped) => println!("{}", instruction_at(&debugger, debugger.sp())),
+            Ok(Stop::Breakpoint(address)) => println!("breakpoint at {}", address),
+            Ok(Stop::Watchpoint {
+                ip,
+                address,
+                old,
+                new,
+            }) => println!("{} wrote {} over {} at {}", ip, new, old, address),
+            Ok(Stop::NeedsInput) => println!("waiting for input"),
+            Ok(Stop::Halted) => println!("halted"),
+            Err(err) => println!("fault: {}", err),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Decoded instructions, so hot loops only decode each instruction once.
+//!
+//! Entries are keyed by address. The memory logs writes to the cells of cached
+//! instructions, and those entries are dropped before the next step, so self-modifying
+//! code still runs correctly. A memory from `restore` or a fresh program has a new
+//! epoch and starts with an empty cache.
+
+use crate::{
+    FaultKind, IntcodeError, IntcodeInput, IntcodeOutput, OpCode, ParameterMode, SuperComputer,
+};
+use std::convert::TryFrom;
+
+// Instructions at or above this address are decoded every time, so a jump to a huge
+// address doesn't allocate a huge cache
+const CACHE_LIMIT: usize = 1 << 16;
+// Longest instruction, op code and three parameters
+const MAX_LEN: usize = 4;
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Op {
+    Standard(OpCode),
+    // Run by its handler in the computer's instruction set
+    Custom(u8),
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Decoded {
+    pub op: Op,
+    // Number of parameters
+    pub arity: u8,
+    pub modes: [ParameterMode; 3],
+    // Raw parameter values, modes not applied
+    pub params: [i64; 3],
+}
+
+#[derive(Default)]
+pub(crate) struct DecodeCache {
+    // Memory epoch and version the entries are up to date with
+    epoch: u64,
+    version: u64,
+    entries: Vec<Option<Decoded>>,
+    // Decode every instruction every time, entries stays empty
+    disabled: bool,
+}
+
+impl DecodeCache {
+    // Forget every entry, for when the instruction set changes
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> SuperComputer<I, O> {
+    /// Turn the decode cache on or off, it's on to start with. Off, every instruction
+    /// is decoded every time it runs, which is only worth it to see what the cache
+    /// saves, as `cargo bench` does.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache.disabled = !enabled;
+        self.cache.entries.clear();
+        self.digits.forget_code();
+    }
+
+    // The instruction at sp, from the cache if it is still valid
+    #[inline]
+    pub(crate) fn decoded(&mut self) -> Result<Decoded, IntcodeError> {
+        // Off, decode straight away rather than by way of the cold miss path
+        if self.cache.disabled {
+            return self.decode();
+        }
+        if self.cache.version == self.digits.version() {
+            if let Some(Some(decoded)) = self.cache.entries.get(self.sp) {
+                return Ok(*decoded);
+            }
+        }
+        self.decode_miss()
+    }
+
+    // Bring the cache up to date with memory, then decode and cache the instruction
+    // if it still isn't there
+    #[cold]
+    fn decode_miss(&mut self) -> Result<Decoded, IntcodeError> {
+        if self.cache.epoch != self.digits.epoch() {
+            self.cache.entries.clear();
+            self.cache.epoch = self.digits.epoch();
+            self.digits.forget_code();
+        } else {
+            self.invalidate();
+        }
+        self.cache.version = self.digits.version();
+        if let Some(Some(decoded)) = self.cache.entries.get(self.sp) {
+            return Ok(*decoded);
+        }
+        let decoded = self.decode()?;
+        if self.sp < CACHE_LIMIT {
+            if self.sp >= self.cache.entries.len() {
+                self.cache.entries.resize(self.sp + 1, None);
+            }
+            self.cache.entries[self.sp] = Some(decoded);
+            self.digits.watch_code(self.sp, decoded.arity as usize + 1);
+        }
+        Ok(decoded)
+    }
+
+    // Drop every entry covering a cell that has been written
+    fn invalidate(&mut self) {
+        for address in self.digits.take_code_writes() {
+            let first = address.saturating_sub(MAX_LEN - 1);
+            let last = (address + 1).min(self.cache.entries.len());
+            for ip in first..last {
+                if let Some(decoded) = self.cache.entries[ip] {
+                    if ip + decoded.arity as usize >= address {
+                        self.cache.entries[ip] = None;
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn decode(&self) -> Result<Decoded, IntcodeError> {
+        let instruction = self.fetch(self.sp as i64)?;
+        let code = instruction % 100;
+        let (op, arity) = match self.isa.get(code) {
+            Some(registered) => match registered.standard_op() {
+                Some(op_code) => (Op::Standard(op_code), op_code.num_params()),
+                // register keeps codes below 100
+                None => (Op::Custom(code as u8), registered.arity()),
+            },
+            None => return Err(self.fault(FaultKind::UnknownOpCode(code))),
+        };
+        let mode = |n: i64| ParameterMode::try_from(n).map_err(|kind: FaultKind| self.fault(kind));
+        let modes = [
+            mode(instruction / 100 % 10)?,
+            mode(instruction / 1000 % 10)?,
+            mode(instruction / 10000 % 10)?,
+        ];
+        let mut params = [0; 3];
+        for (i, param) in params.iter_mut().enumerate().take(arity) {
+            *param = self.fetch((self.sp + i + 1) as i64)?;
+        }
+        Ok(Decoded {
+            op,
+            arity: arity as u8,
+            modes,
+            params,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SuperComputer;
+
+    // OUT #1, then overwrite its parameter with 2 and go round once more
+    const SELF_MODIFYING: [i64; 21] = [
+        104, 1, // 0: OUT #1
+        1101, 0, 2, 1, // 2: ADD #0, #2, [1]
+        1001, 20, -1, 20, // 6: ADD [20], #-1, [20]
+        1005, 20, 0, // 10: JT [20], #0
+        99, 0, 0, 0, 0, 0, 0, // 13: HLT
+        2, // 20: loop count
+    ];
+
+    fn outputs(cached: bool) -> Vec<i64> {
+        let mut computer = SuperComputer::standalone("test".to_string(), &SELF_MODIFYING[..]);
+        computer.set_decode_cache(cached);
+        computer.run().unwrap();
+        computer.output
+    }
+
+    #[test]
+    fn writes_over_cached_instructions() {
+        assert_eq!(outputs(true), vec![1, 2]);
+        assert_eq!(outputs(false), outputs(true));
+    }
+
+    #[test]
+    fn reset_and_restore_drop_stale_entries() {
+        let mut computer = SuperComputer::standalone("test".to_string(), &SELF_MODIFYING[..]);
+        let snapshot = computer.snapshot();
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![1, 2]);
+
+        // Reset puts back the OUT #1 the cache saw as OUT #2
+        computer.output.clear();
+        computer.reset();
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![1, 2]);
+
+        computer.output.clear();
+        computer.restore(&snapshot);
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![1, 2]);
+
+        // A poke from outside counts too
+        computer.output.clear();
+        computer.reset();
+        computer.digits.set(1, 7);
+        computer.digits.set(20, 1);
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![7]);
+    }
+}
@@ -0,0 +1,347 @@
+//! Control flow graphs of Intcode programs, without running them.
+//!
+//! ```no_run
+//! # use intcomputer::{cfg, Program};
+//! # use std::fs;
+//! # fn main() -> std::io::Result<()> {
+//! # let program = Program::load("input.txt")?;
+//! let graph = cfg::build(&program);
+//! fs::write("program.dot", graph.to_dot())?;   // dot -Tsvg program.dot > program.svg
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Code is found the same way as `disasm` finds it, and split into basic blocks at jump
+//! targets and after jumps and halts. Edges are jumps with immediate targets and falling
+//! through to the next instruction. A jump to a computed address, a jump some
+//! instruction writes over, any instruction whose op code gets written over, or one
+//! going to a cell that is only code once written over, has an Unknown edge since where
+//! it goes can't be known without running it. Only writes through position mode are
+//! seen, relative mode writes depend on rb.
+
+use crate::disasm::{self, Instruction, Operand};
+use crate::{OpCode, ParameterMode, Program};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Edge {
+    // A JumpIfTrue or JumpIfFalse with an immediate target
+    Jump(usize),
+    FallThrough(usize),
+    // Indirect or self-modified, could go anywhere
+    Unknown,
+}
+
+/// Straight line code, only entered at the top and only left at the bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    // Address after the last instruction
+    pub end: usize,
+    pub instructions: Vec<Instruction>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cfg {
+    // By start address
+    pub blocks: BTreeMap<usize, Block>,
+    // Names for block starts, from a program's symbols
+    labels: BTreeMap<usize, Vec<String>>,
+}
+
+/// The graph of code reachable from address 0
+pub fn build(program: &[i64]) -> Cfg {
+    build_from(program, &[0])
+}
+
+// Also following code from the entry point, and labelling blocks with symbols
+pub fn build_program(program: &Program) -> Cfg {
+    let mut roots = vec![0];
+    roots.extend(program.entry());
+    let mut cfg = build_from(program, &roots);
+    for (name, address) in program.symbols() {
+        if cfg.blocks.contains_key(address) {
+            cfg.labels.entry(*address).or_default().push(name.clone());
+        }
+    }
+    cfg
+}
+
+pub fn build_from(program: &[i64], roots: &[usize]) -> Cfg {
+    let code = disasm::find_code(program, roots);
+    let ops: Vec<(usize, OpCode, &[Operand])> = code
+        .iter()
+        .flatten()
+        .filter_map(|instruction| match instruction {
+            Instruction::Op {
+                address,
+                op_code,
+                operands,
+            } => Some((*address, *op_code, &operands[..])),
+            _ => None,
+        })
+        .collect();
+
+    // Cells written by position mode parameters, wherever they are
+    let written: HashSet<usize> = ops
+        .iter()
+        .filter_map(|(_, op_code, operands)| {
+            let operand = operands[op_code.write_param()?];
+            match operand.mode {
+                ParameterMode::Pointer if operand.value >= 0 => Some(operand.value as usize),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let decoded: HashSet<usize> = ops.iter().map(|(address, _, _)| *address).collect();
+    let edges: BTreeMap<usize, Vec<Edge>> = ops
+        .iter()
+        .map(|(address, op_code, operands)| {
+            let mut edges = edges(*address, *op_code, operands);
+            let len = operands.len() + 1;
+            let is_jump = matches!(op_code, OpCode::JumpIfTrue | OpCode::JumpIfFalse);
+            let modified = written.contains(address)
+                || (is_jump && (*address..address + len).any(|cell| written.contains(&cell)));
+            // Going to a cell that gets written over before it's code, eg day 5's 1100
+            let into_modified = edges.iter().any(|edge| match edge {
+                Edge::Jump(to) | Edge::FallThrough(to) => {
+                    written.contains(to) && !decoded.contains(to)
+                }
+                Edge::Unknown => false,
+            });
+            if (modified || into_modified) && !edges.contains(&Edge::Unknown) {
+                edges.push(Edge::Unknown);
+            }
+            (*address, edges)
+        })
+        .collect();
+
+    // Blocks start at the roots, jump targets and after anything that doesn't just
+    // fall through
+    let mut leaders: HashSet<usize> = roots.iter().copied().collect();
+    for (address, op_code, operands) in &ops {
+        let next = address + operands.len() + 1;
+        match edges[address][..] {
+            [Edge::FallThrough(to)] if to == next && *op_code != OpCode::Halt => (),
+            _ => {
+                leaders.insert(next);
+            }
+        }
+        for edge in &edges[address] {
+            if let Edge::Jump(target) = edge {
+                leaders.insert(*target);
+            }
+        }
+    }
+
+    let mut cfg = Cfg::default();
+    let mut current: Option<Block> = None;
+    for instruction in code.into_iter().flatten() {
+        let address = instruction.address();
+        let len = instruction.len();
+        let continues = match &current {
+            Some(block) => block.end == address && !leaders.contains(&address),
+            None => false,
+        };
+        if !continues {
+            if let Some(block) = current.take() {
+                cfg.blocks.insert(block.start, block);
+            }
+            current = Some(Block {
+                start: address,
+                end: address,
+                instructions: Vec::new(),
+                edges: Vec::new(),
+            });
+        }
+        let block = current.as_mut().unwrap();
+        block.end = address + len;
+        block.edges = edges[&address].clone();
+        block.instructions.push(instruction);
+    }
+    if let Some(block) = current {
+        cfg.blocks.insert(block.start, block);
+    }
+    cfg
+}
+
+// Where execution can go after an instruction
+fn edges(address: usize, op_code: OpCode, operands: &[Operand]) -> Vec<Edge> {
+    let next = Edge::FallThrough(address + operands.len() + 1);
+    match op_code {
+        OpCode::Halt => vec![],
+        OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+            let (condition, target) = (operands[0], operands[1]);
+            let jump = match target.mode {
+                ParameterMode::Value if target.value >= 0 => Edge::Jump(target.value as usize),
+                _ => Edge::Unknown,
+            };
+            match condition.mode {
+                ParameterMode::Value => {
+                    if (condition.value != 0) == (op_code == OpCode::JumpIfTrue) {
+                        vec![jump]
+                    } else {
+                        vec![next]
+                    }
+                }
+                _ => vec![jump, next],
+            }
+        }
+        _ => vec![next],
+    }
+}
+
+impl Cfg {
+    // The block containing an address
+    pub fn block_at(&self, address: usize) -> Option<&Block> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| address < block.end)
+    }
+
+    // Start addresses of the blocks with an edge to `start`
+    pub fn predecessors(&self, start: usize) -> Vec<usize> {
+        self.blocks
+            .values()
+            .filter(|block| {
+                block.edges.iter().any(|edge| match edge {
+                    Edge::Jump(to) | Edge::FallThrough(to) => *to == start,
+                    Edge::Unknown => false,
+                })
+            })
+            .map(|block| block.start)
+            .collect()
+    }
+
+    /// Graphviz DOT, one box per block listing its instructions. Jumps are solid,
+    /// fall through dashed, and unknown edges go to a single `?` node. Targets with no
+    /// code are drawn as dotted boxes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for name in self.labels.get(&block.start).into_iter().flatten() {
+                label.push_str(&format!("{}:\\l", escape(name)));
+            }
+            for instruction in &block.instructions {
+                label.push_str(&format!("{}\\l", escape(&instruction.to_string())));
+            }
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+        let mut missing = HashSet::new();
+        let mut unknown = false;
+        for block in self.blocks.values() {
+            for edge in &block.edges {
+                let (to, style) = match edge {
+                    Edge::Jump(to) => (format!("b{}", to), ""),
+                    Edge::FallThrough(to) => (format!("b{}", to), " [style=dashed]"),
+                    Edge::Unknown => {
+                        unknown = true;
+                        ("unknown".to_string(), " [style=dotted]")
+                    }
+                };
+                if let Edge::Jump(to) | Edge::FallThrough(to) = edge {
+                    if !self.blocks.contains_key(to) {
+                        missing.insert(*to);
+                    }
+                }
+                writeln!(dot, "    b{} -> {}{};", block.start, to, style).unwrap();
+            }
+        }
+        let mut missing: Vec<usize> = missing.into_iter().collect();
+        missing.sort_unstable();
+        for address in missing {
+            writeln!(
+                dot,
+                "    b{} [label=\"{:04}\", style=dotted];",
+                address, address
+            )
+            .unwrap();
+        }
+        if unknown {
+            dot.push_str("    unknown [label=\"?\", shape=circle];\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // Outputs 2 for any input, and 1 before it for 0
+    const BRANCH: &str = "
+            IN   [x]
+            JT   [x], #skip
+            OUT  #1
+    skip:   OUT  #2
+            HLT
+    x:      db   0
+    ";
+
+    fn edges(cfg: &Cfg) -> Vec<(usize, usize, Vec<Edge>)> {
+        cfg.blocks
+            .values()
+            .map(|block| (block.start, block.end, block.edges.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn conditional_jump_splits_at_its_target() {
+        let cfg = build(&assemble(BRANCH).unwrap());
+        assert_eq!(
+            edges(&cfg),
+            [
+                (0, 5, vec![Edge::Jump(7), Edge::FallThrough(5)]),
+                (5, 7, vec![Edge::FallThrough(7)]),
+                (7, 10, vec![]),
+            ]
+        );
+        assert_eq!(cfg.predecessors(7), [0, 5]);
+        assert_eq!(cfg.block_at(6).map(|block| block.start), Some(5));
+        assert_eq!(cfg.block_at(10), None);
+    }
+
+    #[test]
+    fn writing_over_a_jump_makes_it_unknown() {
+        // The ADD changes where the JT goes
+        let program = assemble("ADD #0, #9, [j+2]\nj: JT #1, #0\nHLT").unwrap();
+        let cfg = build(&program);
+        assert_eq!(edges(&cfg), [(0, 7, vec![Edge::Jump(0), Edge::Unknown])]);
+    }
+
+    #[test]
+    fn dot_snapshot() {
+        let cfg = build(&assemble(BRANCH).unwrap());
+        let expected = r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    b0 [label="0000: IN   [10]\l0002: JT   [10], #7\l"];
+    b5 [label="0005: OUT  #1\l"];
+    b7 [label="0007: OUT  #2\l0009: HLT\l"];
+    b0 -> b7;
+    b0 -> b5 [style=dashed];
+    b5 -> b7 [style=dashed];
+}
+"#;
+        assert_eq!(cfg.to_dot(), expected);
+
+        // A jump to a cell with no code draws a dotted box
+        let cfg = build(&[1105, 1, 100]);
+        assert!(cfg
+            .to_dot()
+            .ends_with("    b0 -> b100;\n    b100 [label=\"0100\", style=dotted];\n}\n"));
+    }
+}
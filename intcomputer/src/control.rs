@@ -0,0 +1,343 @@
+//! Supervising a running `SuperComputer` from another thread.
+//!
+//! ```no_run
+//! # use intcomputer::*;
+//! # use std::sync::mpsc::channel;
+//! # use std::thread;
+//! # let (name, digits) = ("boost".to_string(), Program::load("input.txt").unwrap());
+//! # let ((output, _), (_, input)) = (channel(), channel());
+//! let (controller, control) = control();
+//! thread::spawn(move || SuperComputer::new_command(name, digits, output, input, Some(control)).run());
+//! controller.send(Command::Pause);
+//! if let Some(Response::Status(status)) = controller.send(Command::QueryStatus) {
+//!     println!("stopped at {} after {} instructions", status.sp, status.instructions);
+//! }
+//! controller.send(Command::Resume);
+//! ```
+//!
+//! Commands are handled by `run` between instructions, while it waits for input and
+//! once the program halts. Every command gets exactly one response.
+
+use crate::{FaultKind, HaltReason, IntcodeInput, IntcodeOutput, SuperComputer, TryRead};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+
+// How long to wait for a command before checking the input again
+const INPUT_POLL: Duration = Duration::from_millis(1);
+
+pub enum Command {
+    // Stop executing until Resume, other commands are still answered
+    Pause,
+    Resume,
+    Peek(usize),
+    Poke(usize, i64),
+    QueryStatus,
+    // Start the program over
+    Reset,
+    // Start over with a new program
+    Load(Arc<[i64]>),
+    Quit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub sp: usize,
+    pub rb: i64,
+    pub instructions: u64,
+    pub paused: bool,
+    pub halted: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Response {
+    Paused,
+    Resumed,
+    Value(i64),
+    Poked,
+    Status(Status),
+    Reset,
+    Quit,
+    // A Peek or Poke address past the memory limit
+    Fault(FaultKind),
+}
+
+/// The computer's end of a control channel, give it to `SuperComputer::new_command`
+pub struct Control {
+    commands: Receiver<Command>,
+    responses: Sender<Response>,
+}
+
+/// The supervisor's end of a control channel
+pub struct Controller {
+    commands: Sender<Command>,
+    responses: Receiver<Response>,
+}
+
+pub fn control() -> (Controller, Control) {
+    let (command_send, command_recv) = channel();
+    let (response_send, response_recv) = channel();
+    (
+        Controller {
+            commands: command_send,
+            responses: response_recv,
+        },
+        Control {
+            commands: command_recv,
+            responses: response_send,
+        },
+    )
+}
+
+impl Controller {
+    /// Send a command and wait for the response, None if the computer has gone away
+    pub fn send(&self, command: Command) -> Option<Response> {
+        self.commands.send(command).ok()?;
+        self.responses.recv().ok()
+    }
+}
+
+// What handling a command means for the run loop
+pub(crate) enum Flow {
+    Continue,
+    Reset,
+    Quit,
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> SuperComputer<I, O> {
+    fn command(&mut self, command: Command, halted: bool) -> Flow {
+        let (response, flow) = match command {
+            Command::Pause => {
+                self.paused = true;
+                (Response::Paused, Flow::Continue)
+            }
+            Command::Resume => {
+                self.paused = false;
+                (Response::Resumed, Flow::Continue)
+            }
+            Command::Peek(address) => match self.digits.address(address as i64) {
+                Ok(address) => (Response::Value(self.digits.get(address)), Flow::Continue),
+                Err(kind) => (Response::Fault(kind), Flow::Continue),
+            },
+            Command::Poke(address, value) => match self.digits.address(address as i64) {
+                Ok(address) => {
+                    self.digits.set(address, value);
+                    (Response::Poked, Flow::Continue)
+                }
+                Err(kind) => (Response::Fault(kind), Flow::Continue),
+            },
+            Command::QueryStatus => (
+                Response::Status(Status {
+                    sp: self.sp,
+                    rb: self.rb,
+                    instructions: self.instructions,
+                    paused: self.paused,
+                    halted,
+                }),
+                Flow::Continue,
+            ),
+            Command::Reset => {
+                self.reset();
+                self.paused = false;
+                (Response::Reset, Flow::Reset)
+            }
+            Command::Load(digits) => {
+                self.set_program(digits);
+                self.paused = false;
+                (Response::Reset, Flow::Reset)
+            }
+            Command::Quit => (Response::Quit, Flow::Quit),
+        };
+        if let Some(control) = &self.control {
+            // Nobody listening is fine, the supervisor may only send
+            let _ = control.responses.send(response);
+        }
+        flow
+    }
+
+    /// Handle every waiting command, blocking while paused. Stops early at a Reset or
+    /// Load, which run has to start over from, or a Quit. A supervisor hanging up lets
+    /// the computer carry on without one.
+    pub(crate) fn poll_commands(&mut self) -> Flow {
+        loop {
+            let command = match &self.control {
+                Some(control) if self.paused => control
+                    .commands
+                    .recv()
+                    .map_err(|_| TryRecvError::Disconnected),
+                Some(control) => control.commands.try_recv(),
+                None => return Flow::Continue,
+            };
+            match command {
+                Ok(command) => match self.command(command, false) {
+                    Flow::Continue => (),
+                    flow => return flow,
+                },
+                Err(TryRecvError::Empty) => return Flow::Continue,
+                Err(TryRecvError::Disconnected) => {
+                    self.control = None;
+                    self.paused = false;
+                    return Flow::Continue;
+                }
+            }
+        }
+    }
+
+    /// Wait for the next input while still answering commands. None if a Reset or Load
+    /// started the program over, the input it was waiting for isn't wanted any more.
+    pub(crate) fn read_controlled(&mut self) -> Result<Option<i64>, HaltReason> {
+        loop {
+            // Blocks while paused, so nothing is read until Resume
+            match self.poll_commands() {
+                Flow::Continue => (),
+                Flow::Reset => return Ok(None),
+                Flow::Quit => return Err(HaltReason::Quit),
+            }
+            match self.input.try_read() {
+                TryRead::Value(input) => return Ok(Some(input)),
+                TryRead::Closed => return Err(HaltReason::InputClosed),
+                TryRead::Empty => (),
+            }
+            let command = match &self.control {
+                Some(control) => control.commands.recv_timeout(INPUT_POLL),
+                None => return self.input.read().map(Some).ok_or(HaltReason::InputClosed),
+            };
+            match command {
+                Ok(command) => match self.command(command, false) {
+                    Flow::Continue => (),
+                    Flow::Reset => return Ok(None),
+                    Flow::Quit => return Err(HaltReason::Quit),
+                },
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.control = None;
+                    self.paused = false;
+                }
+            }
+        }
+    }
+
+    /// Answer commands once the program has halted, until a Reset gets it going again
+    pub(crate) fn wait_halted(&mut self) -> Option<HaltReason> {
+        loop {
+            let command = match &self.control {
+                Some(control) => control.commands.recv(),
+                None => return Some(HaltReason::Halted),
+            };
+            match command {
+                Ok(command) => match self.command(command, true) {
+                    Flow::Continue => (),
+                    Flow::Reset => return None,
+                    Flow::Quit => return Some(HaltReason::Quit),
+                },
+                Err(_) => return Some(HaltReason::Halted),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MEMORY_LIMIT;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // Outputs 1, then stores an input in [100] and halts
+    const ECHO: [i64; 5] = [104, 1, 3, 100, 99];
+
+    #[test]
+    fn reset_while_waiting_for_input_starts_over() {
+        let (controller, control) = control();
+        let (output, outputs) = channel();
+        let (inputs, input) = channel();
+        let computer = SuperComputer::new_command(
+            "echo".to_string(),
+            ECHO.to_vec(),
+            output,
+            input,
+            Some(control),
+        );
+        let run = thread::spawn(move || {
+            let mut computer = computer;
+            computer.run()
+        });
+
+        assert_eq!(outputs.recv_timeout(TIMEOUT), Ok(1));
+        assert_eq!(controller.send(Command::Reset), Some(Response::Reset));
+        // Runs from the start again without anyone sending input
+        assert_eq!(outputs.recv_timeout(TIMEOUT), Ok(1));
+        assert_eq!(
+            controller.send(Command::Load(Arc::from(vec![104, 2, 3, 100, 99]))),
+            Some(Response::Reset)
+        );
+        assert_eq!(outputs.recv_timeout(TIMEOUT), Ok(2));
+
+        inputs.send(5).unwrap();
+        loop {
+            match controller.send(Command::QueryStatus) {
+                Some(Response::Status(status)) if status.halted => break,
+                Some(Response::Status(_)) => thread::sleep(INPUT_POLL),
+                response => panic!("unexpected {:?}", response),
+            }
+        }
+        assert_eq!(
+            controller.send(Command::Peek(100)),
+            Some(Response::Value(5))
+        );
+        assert_eq!(controller.send(Command::Quit), Some(Response::Quit));
+        assert_eq!(run.join().unwrap(), Ok(HaltReason::Quit));
+    }
+
+    #[test]
+    fn pause_peek_and_poke() {
+        let (controller, control) = control();
+        let (output, outputs) = channel();
+        let (inputs, input) = channel();
+        let computer = SuperComputer::new_command(
+            "echo".to_string(),
+            ECHO.to_vec(),
+            output,
+            input,
+            Some(control),
+        );
+        let run = thread::spawn(move || {
+            let mut computer = computer;
+            computer.run()
+        });
+
+        assert_eq!(outputs.recv_timeout(TIMEOUT), Ok(1));
+        assert_eq!(controller.send(Command::Pause), Some(Response::Paused));
+        inputs.send(7).unwrap();
+        match controller.send(Command::QueryStatus) {
+            Some(Response::Status(status)) => {
+                assert_eq!((status.sp, status.paused, status.halted), (2, true, false))
+            }
+            response => panic!("unexpected {:?}", response),
+        }
+        // Paused, so the input is still waiting
+        assert_eq!(
+            controller.send(Command::Peek(100)),
+            Some(Response::Value(0))
+        );
+        // Replace the halt with an OUT [100] and a halt after it
+        for (address, value) in [(4, 4), (5, 100), (6, 99)].iter() {
+            assert_eq!(
+                controller.send(Command::Poke(*address, *value)),
+                Some(Response::Poked)
+            );
+        }
+        assert_eq!(
+            controller.send(Command::Peek(DEFAULT_MEMORY_LIMIT)),
+            Some(Response::Fault(FaultKind::AddressOutOfRange(
+                DEFAULT_MEMORY_LIMIT as i64
+            )))
+        );
+        assert_eq!(controller.send(Command::Resume), Some(Response::Resumed));
+        assert_eq!(outputs.recv_timeout(TIMEOUT), Ok(7));
+        // Hanging up on a halted computer lets run return
+        drop(controller);
+        assert_eq!(run.join().unwrap(), Ok(HaltReason::Halted));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{Event, IntcodeError, IntcodeInput, IntcodeOutput, Observer, SuperComputer};
+use std::collections::BTreeSet;
+
+/// Why the debugger handed control back
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stop {
+    // Execution is about to run the instruction at this address
+    Breakpoint(usize),
+    // The instruction at ip wrote new over old at a watched address
+    Watchpoint {
+        ip: usize,
+        address: usize,
+        old: i64,
+        new: i64,
+    },
+    // Nothing interesting, only returned by single steps
+    Stepped,
+    // The computer needs input its input can't give, or halted
+    NeedsInput,
+    Halted,
+}
+
+/// Runs a `SuperComputer` an instruction at a time, stopping on breakpoints and
+/// watchpoints. Inputs and outputs go through the computer's own input and output.
+pub struct Debugger<I, O> {
+    pub computer: SuperComputer<I, O>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+}
+
+// Records the write of the last instruction if it hit a watchpoint
+struct Watcher<'a> {
+    watchpoints: &'a BTreeSet<usize>,
+    hit: Option<(usize, i64, i64)>,
+}
+
+impl<'a> Observer for Watcher<'a> {
+    fn on_write(&mut self, address: usize, old: i64, new: i64) {
+        if self.watchpoints.contains(&address) {
+            self.hit = Some((address, old, new));
+        }
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Debugger<I, O> {
+    pub fn new(computer: SuperComputer<I, O>) -> Debugger<I, O> {
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    pub fn sp(&self) -> usize {
+        self.computer.sp()
+    }
+
+    pub fn rb(&self) -> i64 {
+        self.computer.rb()
+    }
+
+    pub fn memory(&self, start: usize, len: usize) -> Vec<i64> {
+        self.computer.digits.range(start, len)
+    }
+
+    /// Execute one instruction. Breakpoints are ignored, the instruction at sp is run
+    /// even if there is one on it.
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let ip = self.computer.sp();
+        let mut watcher = Watcher {
+            watchpoints: &self.watchpoints,
+            hit: None,
+        };
+        let event = self.computer.step_with(&mut watcher)?;
+        // A custom instruction can both write and output, the output goes out before
+        // stopping on the write
+        if let Some(Event::Output(output)) = event {
+            self.computer.output.write(output);
+        }
+        if let Some((address, old, new)) = watcher.hit {
+            return Ok(Stop::Watchpoint {
+                ip,
+                address,
+                old,
+                new,
+            });
+        }
+        match event {
+            None | Some(Event::Output(_)) => Ok(Stop::Stepped),
+            Some(Event::NeedsInput) => match self.computer.input.read() {
+                Some(input) => {
+                    self.computer.provide_input(input);
+                    self.step()
+                }
+                None => Ok(Stop::NeedsInput),
+            },
+            Some(Event::Halted) => Ok(Stop::Halted),
+        }
+    }
+
+    /// Run until a breakpoint, a watchpoint, halting or running out of input
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        loop {
+            match self.step()? {
+                Stop::Stepped => {
+                    if self.breakpoints.contains(&self.computer.sp()) {
+                        return Ok(Stop::Breakpoint(self.computer.sp()));
+                    }
+                }
+                stop => return Ok(stop),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, OpDef};
+
+    #[test]
+    fn watchpoint_on_an_instruction_that_also_outputs() {
+        // 50 [a]: stores 9 in [a] and outputs it
+        let mut computer = SuperComputer::standalone("test".to_string(), vec![50, 3, 99, 0]);
+        computer.register(
+            50,
+            OpDef::new("PUT", 1, &[0], |context| {
+                context.write(0, 9);
+                Ok(Action::Output(9))
+            }),
+        );
+        let mut debugger = Debugger::new(computer);
+        debugger.add_watchpoint(3);
+        let stop = Stop::Watchpoint {
+            ip: 0,
+            address: 3,
+            old: 0,
+            new: 9,
+        };
+        assert_eq!(debugger.step(), Ok(stop));
+        assert_eq!(debugger.computer.output, [9]);
+        assert_eq!(debugger.step(), Ok(Stop::Halted));
+    }
+}
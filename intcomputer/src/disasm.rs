@@ -0,0 +1,383 @@
+//! Turn an Intcode program back into a readable listing.
+//!
+//! ```text
+//! 0000: ADD  [225], #6, rb+3
+//! 0004: JT   #1, #238
+//! 0007: HLT
+//! 0008: db   "Hello\n"
+//! 0014: db   12, -4, 0
+//! ```
+//!
+//! Code is found by following execution from address 0, and from the entry point of a
+//! program loaded with one: fall through and jumps with immediate targets. The
+//! instruction after an unconditional jump is only followed when its address is stored
+//! as a constant somewhere, eg `ADD #13, #0, rb+0`, which is how compiled Intcode
+//! pushes a return address before a call. Anything never reached is data, printed as
+//! strings when it looks like ASCII text.
+
+use crate::{OpCode, ParameterMode, Program};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Data cells per `db` line
+const DATA_PER_LINE: usize = 8;
+// Shortest run of printable cells shown as a string
+const MIN_TEXT_LEN: usize = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Operand {
+    pub mode: ParameterMode,
+    pub value: i64,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Pointer => write!(f, "[{}]", self.value),
+            ParameterMode::Value => write!(f, "#{}", self.value),
+            ParameterMode::Relative if self.value < 0 => write!(f, "rb{}", self.value),
+            ParameterMode::Relative => write!(f, "rb+{}", self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Op {
+        address: usize,
+        op_code: OpCode,
+        operands: Vec<Operand>,
+    },
+    Data {
+        address: usize,
+        values: Vec<i64>,
+    },
+    Text {
+        address: usize,
+        text: String,
+    },
+}
+
+impl Instruction {
+    pub fn address(&self) -> usize {
+        match self {
+            Instruction::Op { address, .. }
+            | Instruction::Data { address, .. }
+            | Instruction::Text { address, .. } => *address,
+        }
+    }
+
+    // Number of cells covered
+    pub fn len(&self) -> usize {
+        match self {
+            Instruction::Op { operands, .. } => operands.len() + 1,
+            Instruction::Data { values, .. } => values.len(),
+            Instruction::Text { text, .. } => text.chars().count(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}: ", self.address())?;
+        match self {
+            Instruction::Op {
+                op_code, operands, ..
+            } => {
+                if operands.is_empty() {
+                    return write!(f, "{}", op_code.mnemonic());
+                }
+                write!(f, "{:<4} ", op_code.mnemonic())?;
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", operand)?;
+                }
+                Ok(())
+            }
+            Instruction::Data { values, .. } => {
+                write!(f, "db   ")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                Ok(())
+            }
+            Instruction::Text { text, .. } => write!(f, "db   {:?}", text),
+        }
+    }
+}
+
+/// Decode the instruction at `address`, or None if it isn't one. Only canonical
+/// encodings are accepted: no stray mode digits on unused parameters, no immediate
+/// mode write parameters, and every parameter inside the program.
+pub fn decode(program: &[i64], address: usize) -> Option<Instruction> {
+    let instruction = *program.get(address)?;
+    if instruction < 0 {
+        return None;
+    }
+    let op_code = OpCode::try_from(instruction % 100).ok()?;
+    let num_params = op_code.num_params();
+    let mut modes = instruction / 100;
+    let mut operands = Vec::with_capacity(num_params);
+    for i in 0..num_params {
+        let mode = ParameterMode::try_from(modes % 10).ok()?;
+        if op_code.write_param() == Some(i) && mode == ParameterMode::Value {
+            return None;
+        }
+        operands.push(Operand {
+            mode,
+            value: *program.get(address + i + 1)?,
+        });
+        modes /= 10;
+    }
+    if modes != 0 {
+        return None;
+    }
+    Some(Instruction::Op {
+        address,
+        op_code,
+        operands,
+    })
+}
+
+// Addresses execution can continue at after the instruction, and the address after it
+// if it is only reachable as a return address
+fn successors(address: usize, op_code: OpCode, operands: &[Operand]) -> (Vec<i64>, Option<i64>) {
+    let next = (address + op_code.num_params() + 1) as i64;
+    match op_code {
+        OpCode::Halt => (vec![], None),
+        OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+            let mut result = Vec::new();
+            if operands[1].mode == ParameterMode::Value {
+                result.push(operands[1].value);
+            }
+            let always = match operands[0].mode {
+                ParameterMode::Value => (operands[0].value != 0) == (op_code == OpCode::JumpIfTrue),
+                _ => false,
+            };
+            if always {
+                (result, Some(next))
+            } else {
+                result.push(next);
+                (result, None)
+            }
+        }
+        _ => (vec![next], None),
+    }
+}
+
+// A constant stored by `ADD #c, #0, ..` or `MUL #c, #1, ..`
+fn stored_constant(op_code: OpCode, operands: &[Operand]) -> Option<i64> {
+    let identity = match op_code {
+        OpCode::Add => 0,
+        OpCode::Mul => 1,
+        _ => return None,
+    };
+    if operands[0].mode != ParameterMode::Value || operands[1].mode != ParameterMode::Value {
+        return None;
+    }
+    if operands[1].value == identity {
+        Some(operands[0].value)
+    } else if operands[0].value == identity {
+        Some(operands[1].value)
+    } else {
+        None
+    }
+}
+
+// Decode every instruction reachable from the roots
+pub(crate) fn find_code(program: &[i64], roots: &[usize]) -> Vec<Option<Instruction>> {
+    let mut code: Vec<Option<Instruction>> = vec![None; program.len()];
+    let mut covered = vec![false; program.len()];
+    let mut constants = HashSet::new();
+    let mut returns = Vec::new();
+    let mut todo: Vec<i64> = roots.iter().map(|root| *root as i64).collect();
+    while !todo.is_empty() {
+        while let Some(address) = todo.pop() {
+            if address < 0 || address as usize >= program.len() || covered[address as usize] {
+                continue;
+            }
+            let address = address as usize;
+            let instruction = match decode(program, address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            let len = instruction.len();
+            if covered[address..address + len].iter().any(|c| *c) {
+                continue;
+            }
+            if let Instruction::Op {
+                op_code,
+                ref operands,
+                ..
+            } = instruction
+            {
+                let (next, after_jump) = successors(address, op_code, operands);
+                todo.extend(next);
+                returns.extend(after_jump);
+                constants.extend(stored_constant(op_code, operands));
+            }
+            for c in &mut covered[address..address + len] {
+                *c = true;
+            }
+            code[address] = Some(instruction);
+        }
+        // Follow the return sites that turned out to be pushed somewhere
+        let (found, rest): (Vec<i64>, Vec<i64>) = returns
+            .iter()
+            .partition(|address| constants.contains(*address));
+        todo = found;
+        returns = rest;
+    }
+    code
+}
+
+fn is_text(value: i64) -> bool {
+    value == 10 || (32..127).contains(&value)
+}
+
+// Split a run of data cells into strings and plain values
+fn data_lines(program: &[i64], start: usize, end: usize, listing: &mut Vec<Instruction>) {
+    let mut address = start;
+    while address < end {
+        let text_len = program[address..end]
+            .iter()
+            .take_while(|value| is_text(**value))
+            .count();
+        if text_len >= MIN_TEXT_LEN {
+            listing.push(Instruction::Text {
+                address,
+                text: program[address..address + text_len]
+                    .iter()
+                    .map(|value| *value as u8 as char)
+                    .collect(),
+            });
+            address += text_len;
+            continue;
+        }
+        let mut values = Vec::new();
+        while address < end && values.len() < DATA_PER_LINE {
+            let text_len = program[address..end]
+                .iter()
+                .take_while(|value| is_text(**value))
+                .count();
+            if text_len >= MIN_TEXT_LEN {
+                break;
+            }
+            values.push(program[address]);
+            address += 1;
+        }
+        listing.push(Instruction::Data {
+            address: address - values.len(),
+            values,
+        });
+    }
+}
+
+pub fn disassemble(program: &[i64]) -> Vec<Instruction> {
+    disassemble_from(program, &[0])
+}
+
+// Like disassemble, also following code from the entry point of a loaded program
+pub fn disassemble_program(program: &Program) -> Vec<Instruction> {
+    let mut roots = vec![0];
+    roots.extend(program.entry());
+    disassemble_from(program, &roots)
+}
+
+// Like disassemble, following code from each of the roots instead of just 0
+pub fn disassemble_from(program: &[i64], roots: &[usize]) -> Vec<Instruction> {
+    let mut code = find_code(program, roots);
+    let mut listing = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        match code[address].take() {
+            Some(instruction) => {
+                address += instruction.len();
+                listing.push(instruction);
+            }
+            None => {
+                let start = address;
+                while address < program.len() && code[address].is_none() {
+                    address += 1;
+                }
+                data_lines(program, start, address, &mut listing);
+            }
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(program: &[i64]) -> Vec<String> {
+        disassemble(program)
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn code_then_data() {
+        assert_eq!(
+            listing(&[1101, 1, -2, 5, 99, 0, 21101, 3, 4, 5]),
+            [
+                "0000: ADD  #1, #-2, [5]",
+                "0004: HLT",
+                "0005: db   0, 21101, 3, 4, 5"
+            ]
+        );
+    }
+
+    #[test]
+    fn text_needs_a_few_printable_cells() {
+        assert_eq!(
+            listing(&[99, 72, 105, 33, 10, 200, 72, 105]),
+            [
+                "0000: HLT",
+                "0001: db   \"Hi!\\n\"",
+                "0005: db   200, 72, 105"
+            ]
+        );
+    }
+
+    #[test]
+    fn return_sites_only_when_pushed() {
+        // ADD pushes 7 as a return address, so the HLT after the jump is code
+        let call = [1101, 7, 0, 20, 1105, 1, 9, 99, 0, 99];
+        assert_eq!(
+            listing(&call),
+            [
+                "0000: ADD  #7, #0, [20]",
+                "0004: JT   #1, #9",
+                "0007: HLT",
+                "0008: db   0",
+                "0009: HLT"
+            ]
+        );
+        // Nothing stores 7 here, so it's data
+        let mut jump = call;
+        jump[1] = 6;
+        assert_eq!(listing(&jump)[2], "0007: db   99, 0");
+    }
+
+    #[test]
+    fn only_canonical_encodings_decode() {
+        // A mode digit for a parameter ADD doesn't have, an immediate write, a negative
+        // op and a parameter past the end
+        for program in &[&[101101, 1, 2, 3][..], &[11101, 1, 2, 3], &[-1], &[1101, 1]] {
+            assert_eq!(decode(program, 0), None, "{:?}", program);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fmt;
+
+/// What went wrong when a `SuperComputer` faulted
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultKind {
+    /// The low two digits of the instruction are not a known op code
+    UnknownOpCode(i64),
+    /// A parameter mode digit other than 0, 1 or 2
+    InvalidMode(i64),
+    /// A read, write or jump resolved to an address below zero
+    NegativeAddress(i64),
+    /// A read, write or jump resolved to an address past the memory limit
+    AddressOutOfRange(i64),
+    /// The write parameter of an instruction was in immediate mode
+    ImmediateWrite,
+    /// A host call to a function number with nothing registered
+    UnknownHostFunction(i64),
+    /// An Add, Mul, relative address or relative base adjustment overflowed an i64
+    Overflow,
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaultKind::UnknownOpCode(op) => write!(f, "unknown op code {}", op),
+            FaultKind::InvalidMode(mode) => write!(f, "invalid parameter mode {}", mode),
+            FaultKind::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            FaultKind::AddressOutOfRange(addr) => write!(f, "address {} out of range", addr),
+            FaultKind::ImmediateWrite => write!(f, "write to immediate mode parameter"),
+            FaultKind::UnknownHostFunction(function) => {
+                write!(f, "unknown host function {}", function)
+            }
+            FaultKind::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+/// A fault raised by `SuperComputer::run`, with the machine state at the faulting instruction
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IntcodeError {
+    pub ip: usize,
+    pub instruction: i64,
+    pub rb: i64,
+    pub kind: FaultKind,
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at ip {} (instruction {}, rb {})",
+            self.kind, self.ip, self.instruction, self.rb
+        )
+    }
+}
+
+impl Error for IntcodeError {}
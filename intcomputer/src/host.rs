@@ -0,0 +1,98 @@
+//! Calls from an Intcode program into Rust.
+//!
+//! ```no_run
+//! # use intcomputer::SuperComputer;
+//! # let mut computer = SuperComputer::standalone("dice".to_string(), vec![99]);
+//! // Function 1 returns a pseudo random number below its one argument
+//! let mut state: u64 = 42;
+//! computer.register_host(1, 1, move |args| {
+//!     state ^= state << 13;
+//!     state ^= state >> 7;
+//!     state ^= state << 17;
+//!     (state % args[0] as u64) as i64
+//! });
+//! ```
+//!
+//! Programs call host functions with op code 80, `HOST_CALL`:
+//!
+//! ```text
+//! SYS function, [args], [result]      e.g. 180,1,100,50
+//! ```
+//!
+//! The function number is read like any parameter. `args` is the address of the first
+//! argument and `result` where the return value goes, both resolve like write
+//! parameters so they're in position or relative mode. A function gets as many
+//! arguments as it was registered with, and calling one nobody registered faults.
+
+use crate::{Action, FaultKind, IntcodeInput, IntcodeOutput, OpDef, SuperComputer};
+use std::collections::HashMap;
+
+/// The op code `register_host` gives host calls unless it is already taken
+pub const HOST_CALL: i64 = 80;
+
+type Function = dyn FnMut(&[i64]) -> i64 + Send;
+
+pub(crate) struct Host {
+    arity: usize,
+    function: Box<Function>,
+}
+
+pub(crate) type Hosts = HashMap<i64, Host>;
+
+// Used by the handler of the host call instruction
+pub(crate) fn arity(hosts: &Hosts, function: i64) -> Result<usize, FaultKind> {
+    match hosts.get(&function) {
+        Some(host) => Ok(host.arity),
+        None => Err(FaultKind::UnknownHostFunction(function)),
+    }
+}
+
+pub(crate) fn call(hosts: &mut Hosts, function: i64, args: &[i64]) -> Result<i64, FaultKind> {
+    match hosts.get_mut(&function) {
+        Some(host) => Ok((host.function)(args)),
+        None => Err(FaultKind::UnknownHostFunction(function)),
+    }
+}
+
+impl OpDef {
+    // The host call instruction, to register under another op code than HOST_CALL
+    pub fn host_call() -> OpDef {
+        OpDef::new("SYS", 3, &[1, 2], |context| {
+            let function = context.param(0);
+            let start = context.param(1);
+            let arity = context.host_arity(function)?;
+            let mut args = Vec::with_capacity(arity);
+            for i in 0..arity {
+                let address = context.memory().address(start + i as i64)?;
+                args.push(context.memory().get(address));
+            }
+            let result = context.call_host(function, &args)?;
+            context.write(2, result);
+            Ok(Action::Next)
+        })
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> SuperComputer<I, O> {
+    /// Add or replace host function number `function`, called with `arity` arguments.
+    /// Registers the host call instruction as `HOST_CALL` the first time, unless that
+    /// op code already has an instruction.
+    pub fn register_host<F>(&mut self, function: i64, arity: usize, f: F)
+    where
+        F: FnMut(&[i64]) -> i64 + Send + 'static,
+    {
+        if self.instruction_set().get(HOST_CALL).is_none() {
+            self.register(HOST_CALL, OpDef::host_call());
+        }
+        let host = Host {
+            arity,
+            function: Box::new(f),
+        };
+        self.hosts.insert(function, host);
+    }
+
+    // Returns whether there was such a function
+    pub fn remove_host(&mut self, function: i64) -> bool {
+        self.hosts.remove(&function).is_some()
+    }
+}
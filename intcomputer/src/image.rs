@@ -0,0 +1,261 @@
+//! A binary format for programs, much faster to load than text.
+//!
+//! Little endian, version 1:
+//!
+//! | field    | encoding                                                          |
+//! |----------|-------------------------------------------------------------------|
+//! | magic    | the 4 bytes `ICPG`                                                |
+//! | version  | u32, currently 1                                                  |
+//! | flags    | u32, bit 0 set if the cells are varints                           |
+//! | cells    | u64 count, then each cell as an i64, or as a zigzag LEB128 varint |
+//! | sections | to the end of the file, each a u8 kind, u64 byte length and body  |
+//!
+//! Sections are optional and readers skip kinds they don't know:
+//!
+//! | kind | section | body                                                      |
+//! |------|---------|-----------------------------------------------------------|
+//! | 1    | symbols | u64 count, each a u64 address, u32 byte length and UTF-8  |
+//! | 2    | entry   | u64 address execution is meant to start at                |
+//!
+//! Nearly every cell is small, so varints make most programs around a fifth of the size
+//! of plain cells, and half the size of the text.
+
+use crate::state::read_bytes;
+use crate::Program;
+use std::io::{self, Read, Write};
+
+pub(crate) const IMAGE_MAGIC: &[u8; 4] = b"ICPG";
+pub const IMAGE_VERSION: u32 = 1;
+
+const VARINT: u32 = 1;
+const SYMBOLS: u8 = 1;
+const ENTRY: u8 = 2;
+
+/// How `Program::save` writes a program
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    // Comma separated, symbols and entry point are dropped
+    Text,
+    // Binary with every cell 8 bytes
+    Binary,
+    // Binary with varint cells
+    Compact,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn write_varint<W: Write>(writer: &mut W, value: i64) -> io::Result<()> {
+    let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    loop {
+        let byte = (zigzag & 0x7f) as u8;
+        zigzag >>= 7;
+        if zigzag == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<i64> {
+    let mut zigzag = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+        // The tenth byte only has room for the top bit
+        if shift == 63 && byte > 1 {
+            break;
+        }
+        zigzag |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+        }
+    }
+    Err(invalid("varint longer than 64 bits".to_string()))
+}
+
+impl Program {
+    /// Write the binary format, varints if `compact`
+    pub fn write_binary<W: Write>(&self, mut writer: W, compact: bool) -> io::Result<()> {
+        writer.write_all(IMAGE_MAGIC)?;
+        writer.write_all(&IMAGE_VERSION.to_le_bytes())?;
+        let flags = if compact { VARINT } else { 0 };
+        writer.write_all(&flags.to_le_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        for cell in self.iter() {
+            if compact {
+                write_varint(&mut writer, *cell)?;
+            } else {
+                writer.write_all(&cell.to_le_bytes())?;
+            }
+        }
+
+        if !self.symbols().is_empty() {
+            let mut body = Vec::new();
+            body.extend(&(self.symbols().len() as u64).to_le_bytes());
+            for (name, address) in self.symbols() {
+                body.extend(&(*address as u64).to_le_bytes());
+                body.extend(&(name.len() as u32).to_le_bytes());
+                body.extend(name.as_bytes());
+            }
+            write_section(&mut writer, SYMBOLS, &body)?;
+        }
+        if let Some(entry) = self.entry() {
+            write_section(&mut writer, ENTRY, &(entry as u64).to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Read the binary format, either encoding
+    pub fn read_binary<R: Read>(mut reader: R) -> io::Result<Program> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != IMAGE_MAGIC {
+            return Err(invalid("not an intcode image".to_string()));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != IMAGE_VERSION {
+            return Err(invalid(format!("unsupported image version {}", version)));
+        }
+        let compact = read_u32(&mut reader)? & VARINT != 0;
+        let count = read_u64(&mut reader)?;
+        // The count can't be trusted enough to allocate it all up front
+        let mut cells = Vec::with_capacity(count.min(1 << 20) as usize);
+        for _ in 0..count {
+            cells.push(if compact {
+                read_varint(&mut reader)?
+            } else {
+                read_u64(&mut reader)? as i64
+            });
+        }
+        let mut program = Program::new(cells);
+
+        loop {
+            let kind = match read_u8(&mut reader) {
+                Ok(kind) => kind,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(program),
+                Err(err) => return Err(err),
+            };
+            let len = read_u64(&mut reader)?;
+            let mut body = (&mut reader).take(len);
+            match kind {
+                SYMBOLS => {
+                    for _ in 0..read_u64(&mut body)? {
+                        let address = read_u64(&mut body)? as usize;
+                        let name = String::from_utf8(read_bytes(&mut body)?)
+                            .map_err(|_| invalid("symbol is not UTF-8".to_string()))?;
+                        program.add_symbol(&name, address);
+                    }
+                }
+                ENTRY => program.set_entry(Some(read_u64(&mut body)? as usize)),
+                _ => (),
+            }
+            // Skip whatever is left, all of it for an unknown kind
+            io::copy(&mut body, &mut io::sink())?;
+        }
+    }
+}
+
+fn write_section<W: Write>(writer: &mut W, kind: u8, body: &[u8]) -> io::Result<()> {
+    writer.write_all(&[kind])?;
+    writer.write_all(&(body.len() as u64).to_le_bytes())?;
+    writer.write_all(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        let mut program = Program::new(vec![0, 1, -1, 63, -64, 64, 1 << 40, i64::MIN, i64::MAX]);
+        program.add_symbol("start", 0);
+        program.add_symbol("data", 7);
+        program.set_entry(Some(3));
+        program
+    }
+
+    fn binary(program: &Program, compact: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        program.write_binary(&mut bytes, compact).unwrap();
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> io::ErrorKind {
+        Program::read_binary(bytes).unwrap_err().kind()
+    }
+
+    #[test]
+    fn both_encodings_round_trip() {
+        for &compact in &[false, true] {
+            let bytes = binary(&program(), compact);
+            assert_eq!(Program::read_binary(&bytes[..]).unwrap(), program());
+        }
+        let bare = Program::new(vec![1, 2, 3]);
+        assert_eq!(
+            Program::read_binary(&binary(&bare, true)[..]).unwrap(),
+            bare
+        );
+    }
+
+    #[test]
+    fn unknown_sections_are_skipped() {
+        let mut bytes = binary(&Program::new(vec![99]), false);
+        write_section(&mut bytes, 200, b"from the future").unwrap();
+        write_section(&mut bytes, ENTRY, &5u64.to_le_bytes()).unwrap();
+        let program = Program::read_binary(&bytes[..]).unwrap();
+        assert_eq!(&program[..], [99]);
+        assert_eq!(program.entry(), Some(5));
+    }
+
+    #[test]
+    fn corrupt_images_are_errors() {
+        let bytes = binary(&program(), true);
+        assert_eq!(
+            error(&bytes[..bytes.len() - 1]),
+            io::ErrorKind::UnexpectedEof
+        );
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(error(&bad), io::ErrorKind::InvalidData);
+        // A symbol name 4GB long, in a section that says it's short
+        let mut bad = binary(&Program::new(vec![99]), false);
+        let mut body = 1u64.to_le_bytes().to_vec();
+        body.extend(&0u64.to_le_bytes());
+        body.extend(&u32::MAX.to_le_bytes());
+        body.extend(b"start");
+        write_section(&mut bad, SYMBOLS, &body).unwrap();
+        assert_eq!(error(&bad), io::ErrorKind::UnexpectedEof);
+
+        let header = binary(&Program::new(vec![0]), true);
+        let header = &header[..header.len() - 1];
+        // i64::MIN zigzags to u64::MAX, nine bytes of 7 bits then the top bit
+        let mut max = header.to_vec();
+        max.extend(&[0xff; 9]);
+        max.push(0x01);
+        assert_eq!(&Program::read_binary(&max[..]).unwrap()[..], [i64::MIN]);
+        // Bits past the 64th, and an 11th byte
+        for last in &[[0x02, 0x00], [0x81, 0x00]] {
+            let mut long = max[..max.len() - 1].to_vec();
+            long.extend(last);
+            assert_eq!(error(&long), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+/// The result of reading without waiting
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TryRead {
+    Value(i64),
+    // Nothing yet, but there may be later
+    Empty,
+    Closed,
+}
+
+/// Where a `SuperComputer` gets values for its Input instructions
+pub trait IntcodeInput {
+    /// The next value, or None if the source is closed or has nothing left.
+    /// The computer stays parked on the Input instruction when this returns None.
+    fn read(&mut self) -> Option<i64>;
+
+    /// Read without blocking, used while a supervisor may be sending commands.
+    /// Sources that never block can keep the default.
+    fn try_read(&mut self) -> TryRead {
+        match self.read() {
+            Some(value) => TryRead::Value(value),
+            None => TryRead::Closed,
+        }
+    }
+}
+
+/// Where a `SuperComputer` sends the values of its Output instructions
+pub trait IntcodeOutput {
+    /// Returns false if the sink is closed
+    fn write(&mut self, value: i64) -> bool;
+}
+
+impl IntcodeInput for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+
+    fn try_read(&mut self) -> TryRead {
+        match self.try_recv() {
+            Ok(value) => TryRead::Value(value),
+            Err(TryRecvError::Empty) => TryRead::Empty,
+            Err(TryRecvError::Disconnected) => TryRead::Closed,
+        }
+    }
+}
+
+impl IntcodeOutput for Sender<i64> {
+    fn write(&mut self, value: i64) -> bool {
+        self.send(value).is_ok()
+    }
+}
+
+impl IntcodeInput for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl IntcodeOutput for VecDeque<i64> {
+    fn write(&mut self, value: i64) -> bool {
+        self.push_back(value);
+        true
+    }
+}
+
+impl IntcodeOutput for Vec<i64> {
+    fn write(&mut self, value: i64) -> bool {
+        self.push(value);
+        true
+    }
+}
+
+/// Feed the computer from any iterator, eg `IterInput(vec![1, 2].into_iter())`
+pub struct IterInput<I>(pub I);
+
+impl<I: Iterator<Item = i64>> IntcodeInput for IterInput<I> {
+    fn read(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+/// Feed the computer from a closure, return None to close the input
+pub struct FnInput<F>(pub F);
+
+impl<F: FnMut() -> Option<i64>> IntcodeInput for FnInput<F> {
+    fn read(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+/// Hand outputs to a closure, return false to close the output
+pub struct FnOutput<F>(pub F);
+
+impl<F: FnMut(i64) -> bool> IntcodeOutput for FnOutput<F> {
+    fn write(&mut self, value: i64) -> bool {
+        (self.0)(value)
+    }
+}
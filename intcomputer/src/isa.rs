@@ -0,0 +1,340 @@
+//! The instructions a `SuperComputer` understands, and adding your own.
+//!
+//! ```no_run
+//! # use intcomputer::{Action, OpDef, SuperComputer};
+//! # let mut computer = SuperComputer::standalone("debug".to_string(), vec![50, 5, 99]);
+//! // A debug print, op code 50 with one parameter
+//! let print = OpDef::new("DBG", 1, &[], |context| {
+//!     println!("debug: {}", context.param(0));
+//!     Ok(Action::Next)
+//! });
+//! computer.register(50, print);
+//! ```
+//!
+//! Every op code maps to an `OpDef`, giving its mnemonic, how many parameters
+//! follow it, which of those are addresses written to, and what it does. The standard
+//! ten are registered by default, and registering a closure over one of their codes
+//! replaces it. The standard handlers are built in rather than closures, so programs
+//! that only use them run as fast as ever.
+
+use crate::cache::Decoded;
+use crate::host::{self, Hosts};
+use crate::{
+    Event, FaultKind, IntcodeError, IntcodeInput, IntcodeOutput, Memory, Observer, OpCode,
+    ParameterMode, SuperComputer,
+};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+/// What the interpreter does once a custom instruction's handler returns
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    // Store the writes and carry on with the next instruction
+    Next,
+    // Store the writes and carry on at this address
+    Jump(i64),
+    // Store the writes, move on and send this to the output
+    Output(i64),
+    // Stay on the instruction without storing anything, like an Input with nothing
+    // queued. Don't take anything from the input queue before returning this.
+    NeedsInput,
+    // Stay on the instruction and halt
+    Halt,
+}
+
+type Handler = dyn Fn(&mut Context) -> Result<Action, FaultKind> + Send + Sync;
+
+#[derive(Clone)]
+enum Body {
+    Standard(OpCode),
+    Custom(Arc<Handler>),
+}
+
+/// One entry in an `InstructionSet`
+#[derive(Clone)]
+pub struct OpDef {
+    mnemonic: String,
+    arity: usize,
+    writes: Vec<usize>,
+    body: Body,
+}
+
+impl OpDef {
+    /// An instruction followed by `arity` parameters, at most three. Those at the
+    /// positions in `writes` are addresses the handler stores to with
+    /// `Context::write`, the rest are read with their modes applied.
+    pub fn new<F>(mnemonic: &str, arity: usize, writes: &[usize], handler: F) -> OpDef
+    where
+        F: Fn(&mut Context) -> Result<Action, FaultKind> + Send + Sync + 'static,
+    {
+        assert!(arity <= 3, "instructions have at most three parameters");
+        assert!(
+            writes.iter().all(|write| *write < arity),
+            "write parameter past the end of the instruction"
+        );
+        OpDef {
+            mnemonic: mnemonic.to_string(),
+            arity,
+            writes: writes.to_vec(),
+            body: Body::Custom(Arc::new(handler)),
+        }
+    }
+
+    fn standard(op_code: OpCode) -> OpDef {
+        OpDef {
+            mnemonic: op_code.mnemonic().to_string(),
+            arity: op_code.num_params(),
+            writes: op_code.write_param().into_iter().collect(),
+            body: Body::Standard(op_code),
+        }
+    }
+
+    pub fn mnemonic(&self) -> &str {
+        &self.mnemonic
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn writes(&self) -> &[usize] {
+        &self.writes
+    }
+
+    // The op code it behaves as if it's one of the standard ten
+    pub fn standard_op(&self) -> Option<OpCode> {
+        match self.body {
+            Body::Standard(op_code) => Some(op_code),
+            Body::Custom(_) => None,
+        }
+    }
+
+    pub(crate) fn call(&self, context: &mut Context) -> Result<Action, FaultKind> {
+        match &self.body {
+            Body::Custom(handler) => handler(context),
+            // Decoding never hands these out as custom
+            Body::Standard(_) => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Debug for OpDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpDef")
+            .field("mnemonic", &self.mnemonic)
+            .field("arity", &self.arity)
+            .field("writes", &self.writes)
+            .field("standard", &self.standard_op())
+            .finish()
+    }
+}
+
+/// Op codes and the instructions they run
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    // Indexed by op code, 0 to 99
+    instructions: Vec<Option<OpDef>>,
+}
+
+impl Default for InstructionSet {
+    fn default() -> InstructionSet {
+        InstructionSet::standard()
+    }
+}
+
+impl InstructionSet {
+    // The ten op codes every Intcode program uses
+    pub fn standard() -> InstructionSet {
+        let mut set = InstructionSet::empty();
+        for op_code in OpCode::ALL.iter() {
+            set.register(op_code.code(), OpDef::standard(*op_code));
+        }
+        set
+    }
+
+    // No op codes at all
+    pub fn empty() -> InstructionSet {
+        InstructionSet {
+            instructions: vec![None; 100],
+        }
+    }
+
+    /// Add or replace the instruction for an op code, which has to fit in the two
+    /// low digits of an instruction. Returns what it replaced.
+    pub fn register(&mut self, code: i64, instruction: OpDef) -> Option<OpDef> {
+        assert!((0..100).contains(&code), "op codes are 0 to 99");
+        self.instructions[code as usize].replace(instruction)
+    }
+
+    pub fn remove(&mut self, code: i64) -> Option<OpDef> {
+        usize::try_from(code)
+            .ok()
+            .and_then(|code| self.instructions.get_mut(code))
+            .and_then(Option::take)
+    }
+
+    pub fn get(&self, code: i64) -> Option<&OpDef> {
+        usize::try_from(code)
+            .ok()
+            .and_then(|code| self.instructions.get(code))
+            .and_then(Option::as_ref)
+    }
+
+    // Registered op codes in increasing order
+    pub fn codes(&self) -> Vec<i64> {
+        (0..100).filter(|code| self.get(*code).is_some()).collect()
+    }
+}
+
+/// What a custom instruction's handler gets to work with
+pub struct Context<'a> {
+    pub(crate) ip: usize,
+    pub(crate) rb: &'a mut i64,
+    // Values of read parameters, addresses of write parameters
+    pub(crate) params: [i64; 3],
+    pub(crate) arity: usize,
+    pub(crate) writes: &'a [usize],
+    pub(crate) stores: [Option<i64>; 3],
+    pub(crate) input: &'a mut VecDeque<i64>,
+    pub(crate) memory: &'a Memory,
+    pub(crate) hosts: &'a mut Hosts,
+}
+
+impl<'a> Context<'a> {
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn rb(&self) -> i64 {
+        *self.rb
+    }
+
+    pub fn set_rb(&mut self, rb: i64) {
+        *self.rb = rb;
+    }
+
+    /// Parameter `n`, with its mode applied for read parameters, or the address a
+    /// write parameter stores to
+    pub fn param(&self, n: usize) -> i64 {
+        assert!(n < self.arity, "parameter {} of {}", n, self.arity);
+        self.params[n]
+    }
+
+    // Store a value through write parameter `n` once the handler returns
+    pub fn write(&mut self, n: usize, value: i64) {
+        assert!(self.writes.contains(&n), "parameter {} isn't written to", n);
+        self.stores[n] = Some(value);
+    }
+
+    // The next queued input, see Action::NeedsInput for when there is none
+    pub fn read_input(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    pub fn memory(&self) -> &Memory {
+        self.memory
+    }
+
+    // How many arguments a host function takes, see SuperComputer::register_host
+    pub fn host_arity(&self, function: i64) -> Result<usize, FaultKind> {
+        host::arity(self.hosts, function)
+    }
+
+    pub fn call_host(&mut self, function: i64, args: &[i64]) -> Result<i64, FaultKind> {
+        host::call(self.hosts, function, args)
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> SuperComputer<I, O> {
+    /// Add or replace the instruction for an op code on this computer only, returning
+    /// what it replaced. See `InstructionSet::register`.
+    pub fn register(&mut self, code: i64, instruction: OpDef) -> Option<OpDef> {
+        let replaced = Arc::make_mut(&mut self.isa).register(code, instruction);
+        self.cache.clear();
+        replaced
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet {
+        &self.isa
+    }
+
+    // Pass an Arc to share one set between lots of computers
+    pub fn set_instruction_set(&mut self, isa: impl Into<Arc<InstructionSet>>) {
+        self.isa = isa.into();
+        self.cache.clear();
+    }
+
+    // Execute a decoded instruction that isn't one of the standard ten. Kept out of
+    // line so it doesn't slow down the standard ones.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn step_custom<W: Observer>(
+        &mut self,
+        code: u8,
+        decoded: Decoded,
+        observer: &mut W,
+    ) -> Result<Option<Event>, IntcodeError> {
+        // Cloned so the handler can borrow the rest of the computer
+        let isa = self.isa.clone();
+        let instruction = isa
+            .get(code as i64)
+            .ok_or_else(|| self.fault(FaultKind::UnknownOpCode(code as i64)))?;
+        let arity = decoded.arity as usize;
+
+        let mut params = [0; 3];
+        for (i, param) in params.iter_mut().enumerate().take(arity) {
+            let (value, mode) = (decoded.params[i], decoded.modes[i]);
+            *param = if instruction.writes().contains(&i) {
+                self.write_address(value, mode)?
+            } else {
+                match mode {
+                    ParameterMode::Pointer => self.fetch(value)?,
+                    ParameterMode::Relative => self.fetch(self.relative(value)?)?,
+                    ParameterMode::Value => value,
+                }
+            };
+        }
+
+        let ip = self.sp;
+        let mut context = Context {
+            ip,
+            rb: &mut self.rb,
+            params,
+            arity,
+            writes: instruction.writes(),
+            stores: [None; 3],
+            input: &mut self.input_queue,
+            memory: &self.digits,
+            hosts: &mut self.hosts,
+        };
+        let action = instruction.call(&mut context);
+        let stores = context.stores;
+        let action = action.map_err(|kind| self.fault(kind))?;
+
+        let mut event = None;
+        match action {
+            Action::NeedsInput => return Ok(Some(Event::NeedsInput)),
+            Action::Halt => return Ok(Some(Event::Halted)),
+            Action::Next | Action::Jump(_) | Action::Output(_) => {
+                for (address, value) in params.iter().zip(&stores) {
+                    if let Some(value) = value {
+                        self.store(*address, *value, observer)?;
+                    }
+                }
+                self.sp = match action {
+                    Action::Jump(target) => self.jump_target(target)?,
+                    _ => self.sp + arity + 1,
+                };
+                if let Action::Output(value) = action {
+                    self.last_output = Some(value);
+                    event = Some(Event::Output(value));
+                }
+            }
+        }
+        self.instructions += 1;
+        observer.on_custom(ip, code as i64, instruction.mnemonic(), &params[..arity]);
+        Ok(event)
+    }
+}
@@ -1,4 +1,47 @@
 use std::sync::mpsc::{Receiver, Sender,TryRecvError};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod asm;
+pub mod batch;
+mod cache;
+pub mod cfg;
+pub mod control;
+pub mod debugger;
+pub mod disasm;
+mod error;
+pub mod host;
+mod image;
+mod io;
+pub mod isa;
+mod memory;
+pub mod network;
+mod program;
+pub mod scheduler;
+mod state;
+pub mod symbolic;
+pub mod trace;
+pub use asm::assemble;
+pub use control::{control, Command, Control, Controller, Response, Status};
+pub use debugger::{Debugger, Stop};
+pub use disasm::disassemble;
+pub use error::{FaultKind, IntcodeError};
+pub use host::HOST_CALL;
+pub use image::{Format, IMAGE_VERSION};
+pub use io::{FnInput, FnOutput, IntcodeInput, IntcodeOutput, IterInput, TryRead};
+pub use isa::{Action, Context, InstructionSet, OpDef};
+pub use memory::{Memory, DEFAULT_MEMORY_LIMIT, PAGE_SIZE};
+pub use network::{Machine, Monitor, Network, NetworkStop, Packet, Topology};
+pub use program::{ParseError, Program};
+pub use scheduler::{Blocked, Deadlock, Outcome, Scheduler, TaskState};
+pub use state::STATE_VERSION;
+pub use symbolic::{Expr, Linear, SymbolicComputer, SymbolicError};
+pub use trace::{CodeWatcher, CodeWrite, Profiler, Tracer};
+use cache::{DecodeCache, Op};
+use control::Flow;
+use host::Hosts;
 
 
 pub struct Term {
@@ -92,7 +135,7 @@ impl Term {
             self.send(data);
         }
         self.send(10);
-        println!("");
+        println!();
     }
 
     pub fn send_string(&self,s:&str) {
@@ -106,46 +149,114 @@ impl Term {
 
 
 
-// Feature in progress
-use Command::*;
-pub enum Command {
-    Reset(Vec<i64>),
+/// Why `SuperComputer::run` returned without faulting
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HaltReason {
+    Halted,
     Quit,
+    // The input is closed or ran dry, sp is left on the Input instruction
+    InputClosed,
+    OutputClosed,
+    // run_with_limits ran its instructions or time out, running again carries on
+    BudgetExhausted,
+    Timeout,
+}
+
+/// Gets told about what a `SuperComputer` does, see `SuperComputer::step_with`.
+/// The methods are inlined away for `()`, so running without one costs nothing.
+pub trait Observer {
+    // An instruction wrote new over old, called before on_instruction
+    fn on_write(&mut self, _address: usize, _old: i64, _new: i64) {}
+    // An instruction ran, inputs are its parameters after modes are applied.
+    // Not called for a Halt or an Input that had nothing to read, so it sees the same
+    // instructions SuperComputer::instructions counts.
+    fn on_instruction(&mut self, _ip: usize, _op_code: OpCode, _inputs: &[i64]) {}
+    // A registered instruction that isn't one of the standard ten ran, params are all
+    // of its parameters with write parameters as the address written
+    fn on_custom(&mut self, _ip: usize, _code: i64, _mnemonic: &str, _params: &[i64]) {}
+}
+
+impl Observer for () {}
+
+/// What stopped `SuperComputer::run_until_event`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    // The machine is parked on an Input instruction, feed it with provide_input
+    NeedsInput,
+    Output(i64),
+    Halted,
+}
+
+/// The complete state of a `SuperComputer` apart from its name, input and output.
+/// Snapshots share memory pages with the computer and each other until written to,
+/// so keeping lots of them around is cheap.
+#[derive(Clone)]
+pub struct Snapshot {
+    image: Arc<[i64]>,
+    digits: Memory,
+    sp: usize,
+    rb: i64,
+    input_queue: VecDeque<i64>,
+    last_output: Option<i64>,
+    instructions: u64,
 }
 
-#[allow(dead_code)]
-pub struct SuperComputer {
+pub struct SuperComputer<I = Receiver<i64>, O = Sender<i64>> {
     pub name: String,
-    pub digits: Vec<i64>,
+    // The program as loaded, what reset goes back to
+    image: Arc<[i64]>,
+    pub digits: Memory,
     sp: usize,
     rb: i64,
-    output_channel: Sender<i64>,
-    input_channel: Receiver<i64>,
-    command_channel: Option<Receiver<Command>>,
+    pub output: O,
+    pub input: I,
+    input_queue: VecDeque<i64>,
+    control: Option<Control>,
+    paused: bool,
+    // Instructions executed since the program was loaded
+    instructions: u64,
+    cache: DecodeCache,
+    isa: Arc<InstructionSet>,
+    // Functions the program can call with HOST_CALL
+    hosts: Hosts,
     pub last_output: Option<i64>,
 }
 
 
+// Instructions run_with_limits executes between looking at the clock
+const TIME_CHECK: u64 = 4096;
+
 use ParameterMode::*;
-#[derive(Debug,Copy,Clone)]
-enum ParameterMode {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParameterMode {
     Value,
     Pointer,
     Relative,
 }
-impl From<u8> for ParameterMode {
-    fn from(n: u8) -> Self {
+impl ParameterMode {
+    // The digit this mode is encoded as in an instruction
+    pub fn digit(self) -> i64 {
+        match self {
+            Pointer => 0,
+            Value => 1,
+            Relative => 2,
+        }
+    }
+}
+impl TryFrom<i64> for ParameterMode {
+    type Error = FaultKind;
+    fn try_from(n: i64) -> Result<Self, FaultKind> {
         match n {
-            0 => Pointer,
-            1 => Value,
-            2 => Relative,
-            _ => panic!("invalid parameter mode"),
+            0 => Ok(Pointer),
+            1 => Ok(Value),
+            2 => Ok(Relative),
+            _ => Err(FaultKind::InvalidMode(n)),
         }
     }
 }
 
-#[derive(Debug)]
-enum OpCode {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OpCode {
     Add,
     Mul,
     Input,
@@ -158,192 +269,588 @@ enum OpCode {
     RelativeBase,
 }
 use OpCode::*;
-impl From<i64> for OpCode {
-    fn from(n: i64) -> Self {
+impl TryFrom<i64> for OpCode {
+    type Error = FaultKind;
+    fn try_from(n: i64) -> Result<Self, FaultKind> {
         match n {
-            1 => Add,
-            2 => Mul,
-            3 => Input,
-            4 => Output,
-            5 => JumpIfTrue,
-            6 => JumpIfFalse,
-            7 => LessThan,
-            8 => Equals,
-            9 => RelativeBase,
-            99 => Halt,
-            _ => panic!("invalid op code"),
+            1 => Ok(Add),
+            2 => Ok(Mul),
+            3 => Ok(Input),
+            4 => Ok(Output),
+            5 => Ok(JumpIfTrue),
+            6 => Ok(JumpIfFalse),
+            7 => Ok(LessThan),
+            8 => Ok(Equals),
+            9 => Ok(RelativeBase),
+            99 => Ok(Halt),
+            _ => Err(FaultKind::UnknownOpCode(n)),
         }
     }
 }
+impl OpCode {
+    pub const ALL: [OpCode; 10] = [
+        Add,
+        Mul,
+        Input,
+        Output,
+        JumpIfTrue,
+        JumpIfFalse,
+        LessThan,
+        Equals,
+        RelativeBase,
+        Halt,
+    ];
 
+    // The two low digits of an instruction
+    pub fn code(self) -> i64 {
+        match self {
+            Add => 1,
+            Mul => 2,
+            Input => 3,
+            Output => 4,
+            JumpIfTrue => 5,
+            JumpIfFalse => 6,
+            LessThan => 7,
+            Equals => 8,
+            RelativeBase => 9,
+            Halt => 99,
+        }
+    }
 
-impl SuperComputer {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Add => "ADD",
+            Mul => "MUL",
+            Input => "IN",
+            Output => "OUT",
+            JumpIfTrue => "JT",
+            JumpIfFalse => "JF",
+            LessThan => "LT",
+            Equals => "EQ",
+            RelativeBase => "ARB",
+            Halt => "HLT",
+        }
+    }
+
+    // Number of parameters following the instruction, including the one written to
+    pub fn num_params(self) -> usize {
+        match self {
+            Add | Mul | LessThan | Equals => 3,
+            JumpIfTrue | JumpIfFalse => 2,
+            Input | Output | RelativeBase => 1,
+            Halt => 0,
+        }
+    }
+
+    // Index of the parameter holding the address written to, if any
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Add | Mul | LessThan | Equals => Some(2),
+            Input => Some(0),
+            _ => None,
+        }
+    }
+}
+
+
+impl SuperComputer<VecDeque<i64>, Vec<i64>> {
+    // A computer reading from an in memory queue and collecting its outputs in a Vec,
+    // also handy to drive with run_until_event and provide_input
+    pub fn standalone(name: String, digits: impl Into<Arc<[i64]>>) -> Self {
+        SuperComputer::new(name, digits, Vec::new(), VecDeque::new())
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> SuperComputer<I, O> {
     pub fn new(
         name: String,
-        digits: Vec<i64>,
-        output_channel: Sender<i64>,
-        input_channel: Receiver<i64>,
-    ) -> SuperComputer {
-        SuperComputer::new_command(name,digits,output_channel,input_channel,None)
+        digits: impl Into<Arc<[i64]>>,
+        output: O,
+        input: I,
+    ) -> SuperComputer<I, O> {
+        SuperComputer::new_command(name,digits,output,input,None)
     }
 
+    // Pass an Arc to share one copy of the program between lots of computers
     pub fn new_command(
         name: String,
-        mut digits: Vec<i64>,
-        output_channel: Sender<i64>,
-        input_channel: Receiver<i64>,
-        command_channel: Option<Receiver<Command>>,
-    ) -> SuperComputer {
-        // add ram
-        digits.resize(digits.len()*10,0);
+        digits: impl Into<Arc<[i64]>>,
+        output: O,
+        input: I,
+        control: Option<Control>,
+    ) -> SuperComputer<I, O> {
+        let image = digits.into();
         SuperComputer {
             name,
-            digits,
+            digits: Memory::new(&image),
+            image,
             sp: 0,
             rb: 0,
-            output_channel,
-            input_channel,
+            output,
+            input,
+            input_queue: VecDeque::new(),
             last_output: None,
-            command_channel: command_channel
+            control,
+            paused: false,
+            instructions: 0,
+            cache: DecodeCache::default(),
+            isa: Arc::default(),
+            hosts: Hosts::new(),
         }
     }
 
-    pub fn reset(&mut self,digits:&Vec<i64>) {
-        let mut digits = digits.clone();
-        digits.resize(digits.len()*10,0);
+    // Start the program over. Only memory written since it was loaded is restored.
+    pub fn reset(&mut self) {
         self.sp = 0;
         self.rb = 0;
-        self.digits = digits;
+        self.digits.reset(&self.image);
+        self.input_queue.clear();
+        self.instructions = 0;
     }
 
-    pub fn run(&mut self) {
-        loop {
-            let instruction = self.digits[self.sp];
-            let op_code = OpCode::from(instruction % 100);
-
-            //set param modes
-            let param_modes = [
-                ParameterMode::from((instruction / 100 % 10) as u8),
-                ParameterMode::from((instruction / 100 / 10 % 10) as u8),
-                ParameterMode::from((instruction / 100 / 10 / 10 % 10) as u8),
-            ];
-
-            let num_params = match op_code {
-                Add | Mul | LessThan | Equals | JumpIfTrue | JumpIfFalse => 2,
-                Input | Output | RelativeBase => 1,
-                Halt => 0,
-            };
+    // Replace the program and start over, keeping the memory limit
+    pub fn set_program(&mut self, digits: impl Into<Arc<[i64]>>) {
+        self.image = digits.into();
+        self.digits = Memory::with_limit(&self.image, self.digits.limit());
+        self.reset();
+    }
 
-            let mut input_params = Vec::new();
-            for i in 0..num_params {
-                let value = self.digits[self.sp + i + 1];
-                match param_modes[i] {
-                    Pointer => input_params.push(self.digits[value as usize]),
-                    Relative => input_params.push(self.digits[(self.rb + value) as usize]),
-                    Value => input_params.push(value),
-                }
-            }
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
 
-            match op_code {
-                Halt => {
-                    if let Some(command_channel) = &self.command_channel {
-                        match command_channel.recv() {
-                            Ok(command) => {
-                                match command {
-                                    Reset(digits) => {
-                                        self.sp = 0;
-                                        self.rb = 0;
-                                        self.digits = digits.clone();
-                                    }
-                                    Quit => break
-                                }
-                            }
-                            Err(_) => break
-                        }
-                    }
-                    else {
-                        break;
-                    }
+    pub fn rb(&self) -> i64 {
+        self.rb
+    }
+
+    // Instructions executed since the program was loaded or reset, counting every
+    // run, step and resume, but not the Halt at the end. Handy for comparing how
+    // efficient programs are.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    // Takes &mut self to share the written pages, see Memory::share
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.digits.share();
+        Snapshot {
+            image: self.image.clone(),
+            digits: self.digits.clone(),
+            sp: self.sp,
+            rb: self.rb,
+            input_queue: self.input_queue.clone(),
+            last_output: self.last_output,
+            instructions: self.instructions,
+        }
+    }
+
+    // Go back to a snapshot, which can come from any computer
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.image = snapshot.image.clone();
+        self.digits = snapshot.digits.clone();
+        self.sp = snapshot.sp;
+        self.rb = snapshot.rb;
+        self.input_queue = snapshot.input_queue.clone();
+        self.last_output = snapshot.last_output;
+        self.instructions = snapshot.instructions;
+    }
+
+    // Queue a value for the next Input instruction
+    pub fn provide_input(&mut self, input: i64) {
+        self.input_queue.push_back(input);
+    }
+
+    // Number of values given with provide_input not read yet
+    pub fn queued_input(&self) -> usize {
+        self.input_queue.len()
+    }
+
+    // Drive the computer with its input and output until it halts or one of them closes
+    pub fn run(&mut self) -> Result<HaltReason, IntcodeError> {
+        self.run_with(&mut ())
+    }
+
+    // With a control attached commands are handled between instructions as well
+    pub fn run_with<W: Observer>(&mut self, observer: &mut W) -> Result<HaltReason, IntcodeError> {
+        self.run_limited_with(observer, None, None)
+    }
+
+    pub fn run_with_limits(
+        &mut self,
+        max_instructions: Option<u64>,
+        max_duration: Option<Duration>,
+    ) -> Result<HaltReason, IntcodeError> {
+        self.run_limited_with(&mut (), max_instructions, max_duration)
+    }
+
+    /// Like run_with, but gives up with BudgetExhausted once `max_instructions` more
+    /// have executed, or Timeout once `max_duration` has passed. It stops between
+    /// instructions, so the computer can be run again to carry on. Time is checked
+    /// every few thousand instructions and a blocking read isn't interrupted. A Reset or
+    /// Load from the controller starts the instruction budget over along with the
+    /// program.
+    pub fn run_limited_with<W: Observer>(
+        &mut self,
+        observer: &mut W,
+        max_instructions: Option<u64>,
+        max_duration: Option<Duration>,
+    ) -> Result<HaltReason, IntcodeError> {
+        let budget = |instructions: u64| max_instructions.map(|n| instructions.saturating_add(n));
+        let mut budget_end = budget(self.instructions);
+        let deadline = max_duration.map(|duration| Instant::now() + duration);
+        loop {
+            let event = if self.control.is_some() {
+                match self.poll_commands() {
+                    Flow::Quit => return Ok(HaltReason::Quit),
+                    Flow::Reset => budget_end = budget(self.instructions),
+                    Flow::Continue => (),
                 }
-                Add => {
-                    let write_address = (self.digits[self.sp + 3] + self.get_offset(param_modes[2])) as usize;
-                    self.digits[write_address] = input_params[0] + input_params[1];
-                    self.sp += 4;
+                if let Some(reason) = self.limit_reached(budget_end, deadline) {
+                    return Ok(reason);
                 }
-                Mul => {
-                    let write_address = (self.digits[self.sp + 3] + self.get_offset(param_modes[2])) as usize;
-                    self.digits[write_address] = input_params[0] * input_params[1];
-                    self.sp += 4;
+                match self.step_with(observer)? {
+                    Some(event) => event,
+                    None => continue,
                 }
-                Input => {
-                    let write_address = (self.digits[self.sp + 1] + self.get_offset(param_modes[0])) as usize;
-                    match self.input_channel.recv() {
-                        Ok(input) => {
-                            self.digits[write_address] = input;
-                            self.sp += 2;
-                        }
-                        Err(_) => {
-                            //println!("Input channel dead on {}",self.name);
-                            break;
-                        }
-                    }
+            } else if budget_end.is_none() && deadline.is_none() {
+                self.run_until_event_with(observer)?
+            } else {
+                let stop_at = budget_end
+                    .unwrap_or(u64::MAX)
+                    .min(self.instructions.saturating_add(TIME_CHECK));
+                match self.run_until_with(observer, stop_at)? {
+                    Some(event) => event,
+                    None => match self.limit_reached(budget_end, deadline) {
+                        Some(reason) => return Ok(reason),
+                        None => continue,
+                    },
                 }
-                Output => match self.output_channel.send(input_params[0]) {
-                    Ok(_) => {
-                        //println!("Computer {} sent: {}",self.name,input_params[0]);
-                        self.last_output = Some(input_params[0]);
-                        self.sp += 2;
-                    }
-                    Err(_) => {
-                        self.last_output = Some(input_params[0]);
-                        //println!("Output channel dead on {}",self.name);
-                        break;
+            };
+            match event {
+                Event::NeedsInput => match self.read_controlled() {
+                    Ok(Some(input)) => self.provide_input(input),
+                    // Reset, carry on from the start of the program
+                    Ok(None) => budget_end = budget(self.instructions),
+                    Err(reason) => {
+                        //println!("Input channel dead on {}",self.name);
+                        return Ok(reason);
                     }
                 },
-                JumpIfTrue => {
-                    if input_params[0] != 0 {
-                        self.sp = input_params[1] as usize;
-                    } else {
-                        self.sp += 3;
+                Event::Output(output) => {
+                    if !self.output.write(output) {
+                        //println!("Output channel dead on {}",self.name);
+                        return Ok(HaltReason::OutputClosed);
                     }
+                    //println!("Computer {} sent: {}",self.name,output);
                 }
-                JumpIfFalse => {
-                    if input_params[0] == 0 {
-                        self.sp = input_params[1] as usize;
-                    } else {
-                        self.sp += 3;
+                Event::Halted => match self.wait_halted() {
+                    Some(reason) => return Ok(reason),
+                    None => budget_end = budget(self.instructions),
+                },
+            }
+        }
+    }
+
+    // Execute until the computer needs input, produces output or halts.
+    // On NeedsInput and Halted sp is left on the instruction, so calling again is safe.
+    pub fn run_until_event(&mut self) -> Result<Event, IntcodeError> {
+        self.run_until_event_with(&mut ())
+    }
+
+    pub fn run_until_event_with<W: Observer>(&mut self, observer: &mut W) -> Result<Event, IntcodeError> {
+        loop {
+            if let Some(event) = self.step_with(observer)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    // Execute until an event or the instruction count reaches stop_at. Out of line so
+    // it doesn't get in the way of the loop in run_until_event_with.
+    #[inline(never)]
+    fn run_until_with<W: Observer>(&mut self, observer: &mut W, stop_at: u64) -> Result<Option<Event>, IntcodeError> {
+        while self.instructions < stop_at {
+            if let Some(event) = self.step_with(observer)? {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    fn limit_reached(&self, budget_end: Option<u64>, deadline: Option<Instant>) -> Option<HaltReason> {
+        if budget_end.is_some_and(|end| self.instructions >= end) {
+            Some(HaltReason::BudgetExhausted)
+        } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(HaltReason::Timeout)
+        } else {
+            None
+        }
+    }
+
+    // Execute a single instruction, returning the event it caused if any
+    pub fn step(&mut self) -> Result<Option<Event>, IntcodeError> {
+        self.step_with(&mut ())
+    }
+
+    // Execute a single instruction, telling the observer what it does
+    #[inline(always)]
+    pub fn step_with<W: Observer>(&mut self, observer: &mut W) -> Result<Option<Event>, IntcodeError> {
+        let decoded = self.decoded()?;
+        let op_code = match decoded.op {
+            Op::Standard(op_code) => op_code,
+            Op::Custom(code) => return self.step_custom(code, decoded, observer),
+        };
+        let param_modes = decoded.modes;
+
+        let num_params = match op_code.write_param() {
+            Some(_) => op_code.num_params() - 1,
+            None => op_code.num_params(),
+        };
+
+        let mut input_params = [0; 2];
+        for (i, param) in input_params.iter_mut().enumerate().take(num_params) {
+            let value = decoded.params[i];
+            *param = match param_modes[i] {
+                Pointer => self.fetch(value)?,
+                Relative => self.fetch(self.relative(value)?)?,
+                Value => value,
+            };
+        }
+
+        let ip = self.sp;
+        let mut event = None;
+        match op_code {
+            Halt => return Ok(Some(Event::Halted)),
+            Add => {
+                let write_address = self.write_address(decoded.params[2], param_modes[2])?;
+                let value = input_params[0].checked_add(input_params[1]);
+                self.store(write_address, self.checked(value)?, observer)?;
+                self.sp += 4;
+            }
+            Mul => {
+                let write_address = self.write_address(decoded.params[2], param_modes[2])?;
+                let value = input_params[0].checked_mul(input_params[1]);
+                self.store(write_address, self.checked(value)?, observer)?;
+                self.sp += 4;
+            }
+            Input => {
+                let write_address = self.write_address(decoded.params[0], param_modes[0])?;
+                match self.input_queue.pop_front() {
+                    Some(input) => {
+                        self.store(write_address, input, observer)?;
+                        self.sp += 2;
                     }
+                    None => return Ok(Some(Event::NeedsInput)),
                 }
-                LessThan => {
-                    let write_address = (self.digits[self.sp + 3] + self.get_offset(param_modes[2])) as usize;
-                    self.digits[write_address] = if input_params[0] < input_params[1] {
-                        1
-                    } else {
-                        0
-                    };
-                    self.sp += 4;
-                }
-                Equals => {
-                    let write_address = (self.digits[self.sp + 3] + self.get_offset(param_modes[2])) as usize;
-                    self.digits[write_address] = if input_params[0] == input_params[1] {
-                        1
-                    } else {
-                        0
-                    };
-                    self.sp += 4;
+            }
+            Output => {
+                self.last_output = Some(input_params[0]);
+                self.sp += 2;
+                event = Some(Event::Output(input_params[0]));
+            }
+            JumpIfTrue => {
+                if input_params[0] != 0 {
+                    self.sp = self.jump_target(input_params[1])?;
+                } else {
+                    self.sp += 3;
                 }
-                RelativeBase => {
-                    self.rb += input_params[0];
-                    self.sp += 2;
+            }
+            JumpIfFalse => {
+                if input_params[0] == 0 {
+                    self.sp = self.jump_target(input_params[1])?;
+                } else {
+                    self.sp += 3;
                 }
             }
+            LessThan => {
+                let write_address = self.write_address(decoded.params[2], param_modes[2])?;
+                let value = if input_params[0] < input_params[1] {
+                    1
+                } else {
+                    0
+                };
+                self.store(write_address, value, observer)?;
+                self.sp += 4;
+            }
+            Equals => {
+                let write_address = self.write_address(decoded.params[2], param_modes[2])?;
+                let value = if input_params[0] == input_params[1] {
+                    1
+                } else {
+                    0
+                };
+                self.store(write_address, value, observer)?;
+                self.sp += 4;
+            }
+            RelativeBase => {
+                self.rb = self.relative(input_params[0])?;
+                self.sp += 2;
+            }
         }
+        self.instructions += 1;
+        observer.on_instruction(ip, op_code, &input_params[..num_params]);
+        Ok(event)
     }
 
-    fn get_offset(&self, mode: ParameterMode) -> i64 {
+    // Wrap a fault with the state of the instruction at sp
+    fn fault(&self, kind: FaultKind) -> IntcodeError {
+        IntcodeError {
+            ip: self.sp,
+            instruction: self.digits.get(self.sp),
+            rb: self.rb,
+            kind,
+        }
+    }
+
+    fn check_address(&self, address: i64) -> Result<usize, IntcodeError> {
+        self.digits.address(address).map_err(|kind| self.fault(kind))
+    }
+
+    fn fetch(&self, address: i64) -> Result<i64, IntcodeError> {
+        let address = self.check_address(address)?;
+        Ok(self.digits.get(address))
+    }
+
+    fn store<W: Observer>(&mut self, address: i64, value: i64, observer: &mut W) -> Result<(), IntcodeError> {
+        let address = self.check_address(address)?;
+        observer.on_write(address, self.digits.get(address), value);
+        self.digits.set(address, value);
+        Ok(())
+    }
+
+    fn jump_target(&self, address: i64) -> Result<usize, IntcodeError> {
+        self.check_address(address)
+    }
+
+    // Resolve the address written by a parameter
+    fn write_address(&self, param: i64, mode: ParameterMode) -> Result<i64, IntcodeError> {
         match mode {
-            Relative => self.rb,
-            _ => 0,
+            Value => Err(self.fault(FaultKind::ImmediateWrite)),
+            Pointer => Ok(param),
+            Relative => self.relative(param),
         }
     }
+
+    // rb plus an offset, the address a relative parameter points at
+    #[inline]
+    fn relative(&self, offset: i64) -> Result<i64, IntcodeError> {
+        self.checked(self.rb.checked_add(offset))
+    }
+
+    #[inline]
+    fn checked(&self, value: Option<i64>) -> Result<i64, IntcodeError> {
+        value.ok_or_else(|| self.fault(FaultKind::Overflow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a number, doubles it into [100000] and outputs it
+    const DOUBLE: [i64; 9] = [3, 100000, 1002, 100000, 2, 100000, 4, 100000, 99];
+
+    #[test]
+    fn restore_goes_back_to_the_snapshot() {
+        let mut computer = SuperComputer::standalone("double".to_string(), DOUBLE.to_vec());
+        assert_eq!(computer.run_until_event(), Ok(Event::NeedsInput));
+        let snapshot = computer.snapshot();
+
+        computer.provide_input(21);
+        assert_eq!(computer.run_until_event(), Ok(Event::Output(42)));
+        assert_eq!(computer.digits.get(100000), 42);
+        assert_eq!(computer.instructions(), 3);
+
+        computer.restore(&snapshot);
+        assert_eq!((computer.sp(), computer.instructions()), (0, 0));
+        assert_eq!(computer.digits.get(100000), 0);
+        assert_eq!(computer.run_until_event(), Ok(Event::NeedsInput));
+        computer.provide_input(5);
+        assert_eq!(computer.run_until_event(), Ok(Event::Output(10)));
+
+        // The snapshot kept its pages even though the computer wrote to them
+        let mut other = SuperComputer::standalone("other".to_string(), Vec::new());
+        other.restore(&snapshot);
+        assert_eq!(other.digits.get(100000), 0);
+        assert_eq!(other.digits.range(0, DOUBLE.len()), DOUBLE);
+    }
+
+    #[test]
+    fn reset_clears_dirty_pages() {
+        let mut computer = SuperComputer::standalone("double".to_string(), DOUBLE.to_vec());
+        computer.provide_input(4);
+        computer.run().unwrap();
+        assert_eq!(computer.output, [8]);
+        assert_eq!(computer.digits.dirty_pages(), 1);
+
+        computer.reset();
+        assert_eq!(computer.digits.dirty_pages(), 0);
+        assert_eq!(computer.digits.get(100000), 0);
+        assert_eq!(computer.instructions(), 0);
+        computer.provide_input(6);
+        computer.run().unwrap();
+        assert_eq!(computer.output, [8, 12]);
+    }
+
+    // Counts [n] down to zero, two instructions a time round, then outputs 7
+    const COUNTDOWN: &str = "
+    loop:   ADD  [n], #-1, [n]
+            JT   [n], #loop
+            OUT  #7
+            HLT
+    n:      db   1000
+    ";
+
+    #[test]
+    fn budget_stops_exactly_and_resumes() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let mut unlimited = SuperComputer::standalone("countdown".to_string(), program.clone());
+        assert_eq!(unlimited.run(), Ok(HaltReason::Halted));
+        assert_eq!((unlimited.instructions(), &unlimited.output[..]), (2001, &[7][..]));
+
+        let mut computer = SuperComputer::standalone("countdown".to_string(), program);
+        assert_eq!(computer.run_with_limits(Some(777), None), Ok(HaltReason::BudgetExhausted));
+        assert_eq!(computer.instructions(), 777);
+        assert!(computer.output.is_empty());
+        assert_eq!(computer.run_with_limits(Some(777), None), Ok(HaltReason::BudgetExhausted));
+        assert_eq!(computer.instructions(), 1554);
+        assert_eq!(computer.run_with_limits(None, None), Ok(HaltReason::Halted));
+        assert_eq!(computer.instructions(), unlimited.instructions());
+        assert_eq!(computer.output, unlimited.output);
+    }
+
+    #[test]
+    fn timeout_on_a_jump_to_itself() {
+        let mut computer = SuperComputer::standalone("spin".to_string(), vec![1105, 1, 0]);
+        let limit = Duration::from_millis(20);
+        assert_eq!(computer.run_with_limits(None, Some(limit)), Ok(HaltReason::Timeout));
+        assert!(computer.instructions() > 0);
+        assert_eq!(computer.sp(), 0);
+    }
+
+    #[test]
+    fn reset_starts_the_budget_over() {
+        use std::sync::mpsc::channel;
+        use std::thread;
+
+        let timeout = Duration::from_secs(5);
+        let program = assemble("ADD #0, #0, [x]\nOUT #1\nIN [x]\nHLT\nx: db 0").unwrap();
+        let (controller, control) = control();
+        let (output, outputs) = channel();
+        let (inputs, input) = channel();
+        let mut computer = SuperComputer::new_command("reset".to_string(), program, output, input, Some(control));
+        let run = thread::spawn(move || {
+            assert_eq!(computer.run_with_limits(Some(1), None), Ok(HaltReason::BudgetExhausted));
+            // The budget ends at instruction 4, but a reset counts from 0 again, and
+            // the IN is the third instruction after it
+            computer.run_with_limits(Some(3), None).map(|reason| (reason, computer.instructions()))
+        });
+        assert_eq!(outputs.recv_timeout(timeout), Ok(1));
+        assert_eq!(controller.send(Command::Reset), Some(Response::Reset));
+        assert_eq!(outputs.recv_timeout(timeout), Ok(1));
+        inputs.send(5).unwrap();
+        // Without a controller a halt would end the run rather than wait for commands
+        drop(controller);
+        assert_eq!(run.join().unwrap(), Ok((HaltReason::BudgetExhausted, 3)));
+    }
 }
@@ -0,0 +1,493 @@
+use crate::FaultKind;
+use std::collections::{HashMap, HashSet};
+use std::ops::Index;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const PAGE_BITS: usize = 10;
+pub const PAGE_SIZE: usize = 1 << PAGE_BITS;
+// Pages below this live in a Vec indexed by page, grown a page at a time as it's
+// written, so the program and the cells near it are one lookup away
+const LOW_PAGES: usize = 64;
+// Pages below this are looked up in a Vec, pages above it in a HashMap so a stray
+// write to a huge address doesn't allocate a huge page table
+const DENSE_PAGES: usize = 1 << 12;
+/// Default number of addressable cells, 16M cells or 128MB if every page gets touched
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+static ZERO: i64 = 0;
+static EPOCHS: AtomicU64 = AtomicU64::new(0);
+
+fn next_epoch() -> u64 {
+    EPOCHS.fetch_add(1, Ordering::Relaxed)
+}
+
+type Cells = [i64; PAGE_SIZE];
+
+// Writing to an owned page is a plain store. Shared pages belong to clones as well and
+// are copied into an owned one by the first write, checking an Arc's count on every
+// write would cost more than the rest of the instruction.
+#[derive(Clone)]
+enum Page {
+    Owned(Box<Cells>),
+    Shared(Arc<Cells>),
+}
+
+impl Page {
+    #[inline]
+    fn cells(&self) -> &Cells {
+        match self {
+            Page::Owned(cells) => cells,
+            Page::Shared(cells) => cells,
+        }
+    }
+
+    #[inline]
+    fn cells_mut(&mut self) -> &mut Cells {
+        match self {
+            Page::Owned(cells) => cells,
+            Page::Shared(_) => self.unshare(),
+        }
+    }
+
+    // Out of line so the copy doesn't put a page on the stack of every write
+    #[cold]
+    #[inline(never)]
+    fn unshare(&mut self) -> &mut Cells {
+        let cells = Box::new(*self.cells());
+        *self = Page::Owned(cells);
+        match self {
+            Page::Owned(cells) => cells,
+            Page::Shared(_) => unreachable!(),
+        }
+    }
+
+    fn share(&mut self) {
+        if let Page::Owned(cells) = self {
+            *self = Page::Shared(Arc::new(**cells));
+        }
+    }
+}
+
+fn new_page() -> Page {
+    Page::Owned(Box::new([0; PAGE_SIZE]))
+}
+
+/// Intcode memory, pages of `PAGE_SIZE` cells allocated the first time they are
+/// written to. The low 64 pages are found by indexing a Vec, past that in a page table.
+/// Untouched cells read as 0. Addresses at or past the limit are a fault.
+///
+/// Cloning copies the pages written since the last `share` and shares the rest. A
+/// shared page is copied the first time either side writes to it. Call `share` first
+/// to make a clone cheap.
+///
+/// Pages written since the program was loaded are remembered, so `reset` only has to
+/// put those back.
+pub struct Memory {
+    // Pages from address 0, none missing
+    low: Vec<Page>,
+    dense: Vec<Option<Page>>,
+    sparse: HashMap<usize, Page>,
+    limit: usize,
+    // Written pages, each listed once
+    dirty: Vec<usize>,
+    dirty_low: Vec<bool>,
+    dirty_dense: Vec<bool>,
+    dirty_sparse: HashSet<usize>,
+    // Cells of instructions in the computer's decode cache. Writing one logs it in
+    // code_writes and bumps the version. A new epoch means the cache doesn't belong to
+    // this memory.
+    watched: Vec<bool>,
+    code_writes: Vec<usize>,
+    epoch: u64,
+    version: u64,
+}
+
+impl Clone for Memory {
+    // The clone starts a new epoch, nothing has decoded it yet
+    fn clone(&self) -> Memory {
+        let epoch = next_epoch();
+        Memory {
+            low: self.low.clone(),
+            dense: self.dense.clone(),
+            sparse: self.sparse.clone(),
+            limit: self.limit,
+            dirty: self.dirty.clone(),
+            dirty_low: self.dirty_low.clone(),
+            dirty_dense: self.dirty_dense.clone(),
+            dirty_sparse: self.dirty_sparse.clone(),
+            watched: Vec::new(),
+            code_writes: Vec::new(),
+            epoch,
+            version: epoch,
+        }
+    }
+}
+
+impl Memory {
+    pub fn new(program: &[i64]) -> Memory {
+        Memory::with_limit(program, DEFAULT_MEMORY_LIMIT)
+    }
+
+    pub fn with_limit(program: &[i64], limit: usize) -> Memory {
+        let epoch = next_epoch();
+        let mut memory = Memory {
+            low: Vec::new(),
+            dense: Vec::new(),
+            sparse: HashMap::new(),
+            limit,
+            dirty: Vec::new(),
+            dirty_low: Vec::new(),
+            dirty_dense: Vec::new(),
+            dirty_sparse: HashSet::new(),
+            watched: Vec::new(),
+            code_writes: Vec::new(),
+            epoch,
+            version: epoch,
+        };
+        for (page, chunk) in program.chunks(PAGE_SIZE).enumerate() {
+            memory.page_mut(page)[..chunk.len()].copy_from_slice(chunk);
+        }
+        memory.mark_clean();
+        memory
+    }
+
+    /// Put memory back to `program`, which must be the program it was created with.
+    /// Only pages written since then are touched, pages past the end of the program
+    /// are freed.
+    pub fn reset(&mut self, program: &[i64]) {
+        // page_mut won't list them again, the flags are still set
+        let dirty = std::mem::take(&mut self.dirty);
+        for &page in &dirty {
+            let start = page << PAGE_BITS;
+            if start < program.len() {
+                let chunk = &program[start..program.len().min(start + PAGE_SIZE)];
+                self.log_code_writes(start, chunk);
+                let cells = self.page_mut(page);
+                cells[..chunk.len()].copy_from_slice(chunk);
+                for cell in &mut cells[chunk.len()..] {
+                    *cell = 0;
+                }
+            } else if page < LOW_PAGES {
+                // Dropped below by truncating the low pages
+                self.log_code_writes(start, &[]);
+            } else if page < DENSE_PAGES {
+                self.log_code_writes(start, &[]);
+                self.dense[page] = None;
+            } else {
+                self.sparse.remove(&page);
+            }
+        }
+        // Low pages past the program that weren't written are still zero
+        let program_pages = (program.len() + PAGE_SIZE - 1) >> PAGE_BITS;
+        if self.low.len() > program_pages {
+            self.low.truncate(program_pages);
+            self.dirty_low.truncate(program_pages);
+        }
+        self.dirty = dirty;
+        self.mark_clean();
+    }
+
+    // Before a page is put back, log its decoded cells that are about to change
+    fn log_code_writes(&mut self, start: usize, chunk: &[i64]) {
+        let end = self.watched.len().min(start + PAGE_SIZE);
+        for address in start..end {
+            let new = chunk.get(address - start).copied().unwrap_or(0);
+            if self.watched[address] && self[address] != new {
+                self.log_code_write(address);
+            }
+        }
+    }
+
+    #[cold]
+    fn log_code_write(&mut self, address: usize) {
+        self.watched[address] = false;
+        self.code_writes.push(address);
+        self.version = next_epoch();
+    }
+
+    pub(crate) fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    // Changes whenever epoch does or a watched cell is written
+    #[inline]
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    // Log writes to these cells until they are written or forget_code is called
+    pub(crate) fn watch_code(&mut self, start: usize, len: usize) {
+        if start + len > self.watched.len() {
+            self.watched.resize(start + len, false);
+        }
+        for watched in &mut self.watched[start..start + len] {
+            *watched = true;
+        }
+    }
+
+    pub(crate) fn take_code_writes(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.code_writes)
+    }
+
+    pub(crate) fn forget_code(&mut self) {
+        self.watched.clear();
+        self.code_writes.clear();
+    }
+
+    // Make every page shared, so clones share them until written
+    pub fn share(&mut self) {
+        for page in &mut self.low {
+            page.share();
+        }
+        for page in self.dense.iter_mut().flatten() {
+            page.share();
+        }
+        for page in self.sparse.values_mut() {
+            page.share();
+        }
+    }
+
+    // Number of pages written since the program was loaded or reset
+    pub fn dirty_pages(&self) -> usize {
+        self.dirty.len()
+    }
+
+    fn mark_clean(&mut self) {
+        for page in self.dirty.drain(..) {
+            if page < LOW_PAGES {
+                if let Some(dirty) = self.dirty_low.get_mut(page) {
+                    *dirty = false;
+                }
+            } else if page < DENSE_PAGES {
+                self.dirty_dense[page] = false;
+            }
+        }
+        self.dirty_sparse.clear();
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // Lowering the limit doesn't free pages that are already allocated
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    // Number of cells currently backed by a page
+    pub fn allocated(&self) -> usize {
+        let pages = self.dense.iter().filter(|page| page.is_some()).count() + self.sparse.len();
+        (self.low.len() + pages) * PAGE_SIZE
+    }
+
+    // Every allocated page as (address of its first cell, cells), lowest first
+    pub fn pages(&self) -> Vec<(usize, &[i64])> {
+        let mut pages: Vec<(usize, &[i64])> =
+            self.low
+                .iter()
+                .map(|page| &page.cells()[..])
+                .enumerate()
+                .chain(self.dense.iter().enumerate().filter_map(|(page, cells)| {
+                    cells.as_ref().map(|cells| (page, &cells.cells()[..]))
+                }))
+                .chain(
+                    self.sparse
+                        .iter()
+                        .map(|(page, cells)| (*page, &cells.cells()[..])),
+                )
+                .map(|(page, cells)| (page << PAGE_BITS, cells))
+                .collect();
+        pages.sort_by_key(|(address, _)| *address);
+        pages
+    }
+
+    /// Check an address computed by a program against the limit
+    #[inline]
+    pub fn address(&self, address: i64) -> Result<usize, FaultKind> {
+        if address < 0 {
+            Err(FaultKind::NegativeAddress(address))
+        } else if address as u64 >= self.limit as u64 {
+            Err(FaultKind::AddressOutOfRange(address))
+        } else {
+            Ok(address as usize)
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, address: usize) -> i64 {
+        self[address]
+    }
+
+    // Allocates the page if needed, the limit is only enforced by address()
+    #[inline]
+    pub fn set(&mut self, address: usize, value: i64) {
+        if address < self.watched.len() && self.watched[address] {
+            self.log_code_write(address);
+        }
+        let page = address >> PAGE_BITS;
+        if let Some(cells) = self.low.get_mut(page) {
+            cells.cells_mut()[address & (PAGE_SIZE - 1)] = value;
+            if !self.dirty_low[page] {
+                self.dirty_low[page] = true;
+                self.dirty.push(page);
+            }
+        } else {
+            self.page_mut(address >> PAGE_BITS)[address & (PAGE_SIZE - 1)] = value;
+        }
+    }
+
+    // Stops at the end of the address space rather than wrapping around
+    pub fn range(&self, start: usize, len: usize) -> Vec<i64> {
+        (start..start.saturating_add(len))
+            .map(|address| self.get(address))
+            .collect()
+    }
+
+    #[inline]
+    fn page(&self, page: usize) -> Option<&Page> {
+        if page < DENSE_PAGES {
+            self.dense.get(page).and_then(|page| page.as_ref())
+        } else {
+            self.sparse.get(&page)
+        }
+    }
+
+    // The cells of a page to write to, growing the low pages to cover it if it's low
+    // enough. Anything past them is out of line, most writes never get here.
+    #[inline(never)]
+    fn page_mut(&mut self, page: usize) -> &mut [i64] {
+        if page < LOW_PAGES {
+            if page >= self.low.len() {
+                self.low.resize_with(page + 1, new_page);
+                self.dirty_low.resize(page + 1, false);
+            }
+            if !self.dirty_low[page] {
+                self.dirty_low[page] = true;
+                self.dirty.push(page);
+            }
+            return self.low[page].cells_mut();
+        }
+        let cells = if page < DENSE_PAGES {
+            if page >= self.dense.len() {
+                self.dense.resize_with(page + 1, || None);
+                self.dirty_dense.resize(page + 1, false);
+            }
+            if !self.dirty_dense[page] {
+                self.dirty_dense[page] = true;
+                self.dirty.push(page);
+            }
+            self.dense[page].get_or_insert_with(new_page)
+        } else {
+            if self.dirty_sparse.insert(page) {
+                self.dirty.push(page);
+            }
+            self.sparse.entry(page).or_insert_with(new_page)
+        };
+        cells.cells_mut()
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    #[inline]
+    fn index(&self, address: usize) -> &i64 {
+        if let Some(page) = self.low.get(address >> PAGE_BITS) {
+            return &page.cells()[address & (PAGE_SIZE - 1)];
+        }
+        match self.page(address >> PAGE_BITS) {
+            Some(page) => &page.cells()[address & (PAGE_SIZE - 1)],
+            None => &ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One address in a low page, one in a dense page and one in a sparse page
+    const ADDRESSES: [usize; 3] = [100, LOW_PAGES * PAGE_SIZE + 5, DENSE_PAGES * PAGE_SIZE + 7];
+
+    #[test]
+    fn reset_only_undoes_dirty_pages() {
+        let program = [1, 2, 3];
+        let mut memory = Memory::new(&program);
+        assert_eq!(memory.dirty_pages(), 0);
+        let allocated = memory.allocated();
+        for (i, address) in ADDRESSES.iter().enumerate() {
+            memory.set(*address, 10 + i as i64);
+            memory.set(*address + 1, 20 + i as i64);
+        }
+        assert_eq!(memory.dirty_pages(), 3);
+        assert_eq!(memory.get(ADDRESSES[2] + 1), 22);
+
+        memory.reset(&program);
+        assert_eq!(memory.dirty_pages(), 0);
+        assert_eq!(memory.allocated(), allocated);
+        assert_eq!(memory.range(0, 4), [1, 2, 3, 0]);
+        for address in &ADDRESSES[1..] {
+            assert_eq!(memory.range(*address, 2), [0, 0]);
+        }
+
+        memory.set(2, 30);
+        assert_eq!(memory.dirty_pages(), 1);
+        memory.reset(&program);
+        assert_eq!(memory.range(0, 3), program);
+    }
+
+    #[test]
+    fn clones_share_pages_until_written() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+        for address in &ADDRESSES {
+            memory.set(*address, 1);
+        }
+        memory.share();
+        let mut clone = memory.clone();
+        for address in &ADDRESSES {
+            clone.set(*address, 2);
+            memory.set(*address + 1, 3);
+        }
+        for address in &ADDRESSES {
+            assert_eq!(memory.range(*address, 2), [1, 3]);
+            assert_eq!(clone.range(*address, 2), [2, 0]);
+        }
+    }
+
+    // Whether the page holding address is the same allocation in both
+    fn same_page(a: &Memory, b: &Memory, address: usize) -> bool {
+        let page = |memory: &Memory| {
+            let page = address >> PAGE_BITS;
+            let page = match memory.low.get(page) {
+                Some(page) => page,
+                None => memory.page(page).unwrap(),
+            };
+            page.cells().as_ptr()
+        };
+        page(a) == page(b)
+    }
+
+    #[test]
+    fn shared_pages_are_not_copied() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+        for address in &ADDRESSES {
+            memory.set(*address, 1);
+        }
+        let copy = memory.clone();
+        memory.share();
+        let clone = memory.clone();
+        for address in &ADDRESSES {
+            assert!(!same_page(&memory, &copy, *address));
+            assert!(same_page(&memory, &clone, *address));
+        }
+
+        // Only the written page stops being shared
+        memory.set(ADDRESSES[0] + 1, 5);
+        assert!(!same_page(&memory, &clone, ADDRESSES[0]));
+        assert!(same_page(&memory, &clone, ADDRESSES[1]));
+        let again = memory.clone();
+        assert!(!same_page(&memory, &again, ADDRESSES[0]));
+        assert!(same_page(&memory, &again, ADDRESSES[2]));
+    }
+}
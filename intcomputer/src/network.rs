@@ -0,0 +1,382 @@
+//! Several computers wired together and run in turn on the calling thread.
+//!
+//! ```no_run
+//! # use intcomputer::network::{Network, Topology};
+//! # use intcomputer::Program;
+//! # fn main() -> Result<(), intcomputer::IntcodeError> {
+//! # let (program, phases) = (Program::load("input.txt").unwrap(), [9, 8, 7, 6, 5]);
+//! // Five amplifiers feeding each other in a loop, each first given its phase
+//! let mut network = Network::new(&program, 5, Topology::Ring);
+//! for (i, phase) in phases.iter().enumerate() {
+//!     network.send(i, *phase);
+//! }
+//! network.send(0, 0);
+//! network.run()?;
+//! let thrust = network.machines[4].last_output;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Machines take turns running until they need input nobody has sent yet or halt, so
+//! the result never depends on timing. Outputs a topology has nowhere to send stay in
+//! the machine's own output.
+
+use crate::{Event, IntcodeError, SuperComputer};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// A computer in a network
+pub type Machine = SuperComputer<VecDeque<i64>, Vec<i64>>;
+
+/// How the outputs of each machine are delivered
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Topology {
+    // Machine i feeds i + 1, the outputs of the last one leave the network
+    Chain,
+    // Like Chain with the last machine feeding the first
+    Ring,
+    // Machine 0 feeds every other machine, and all of them feed machine 0
+    Star,
+    // Every machine feeds every other one
+    Mesh,
+    // Outputs are (dest, x, y) packets, x and y go to machine dest. Packets for
+    // addresses outside the network go to the Monitor.
+    Packets,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Sees the packets that leave a network and decides what happens when it goes idle,
+/// like the NAT of Advent of Code day 23
+pub trait Monitor {
+    // A packet for an address with no machine, return false to stop the network
+    fn on_packet(&mut self, _packet: Packet) -> bool {
+        true
+    }
+    // Every machine is waiting on input. Return a packet to wake the network with,
+    // or None to stop.
+    fn on_idle(&mut self) -> Option<Packet> {
+        None
+    }
+}
+
+impl Monitor for () {}
+
+/// Why `Network::run` returned
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NetworkStop {
+    // Every machine halted
+    Halted,
+    // Every machine still running is waiting on input and the monitor had none
+    Idle,
+    // The monitor asked to stop
+    Stopped,
+}
+
+pub struct Network {
+    pub machines: Vec<Machine>,
+    topology: Topology,
+    // Given to a machine waiting on an empty queue, once a turn
+    idle_input: Option<i64>,
+    // Packet values sent so far by each machine
+    partial: Vec<Vec<i64>>,
+    halted: Vec<bool>,
+}
+
+impl Network {
+    // `size` copies of the same program, sharing one image to reset to
+    pub fn new(program: &[i64], size: usize, topology: Topology) -> Network {
+        let image: Arc<[i64]> = Arc::from(program);
+        let machines = (0..size)
+            .map(|i| SuperComputer::standalone(i.to_string(), image.clone()))
+            .collect();
+        Network::from_machines(machines, topology)
+    }
+
+    pub fn from_machines(machines: Vec<Machine>, topology: Topology) -> Network {
+        Network {
+            partial: vec![Vec::new(); machines.len()],
+            halted: vec![false; machines.len()],
+            machines,
+            topology,
+            idle_input: None,
+        }
+    }
+
+    /// Machines waiting on an empty queue read `value` instead of blocking, once a
+    /// turn. Day 23 machines expect -1.
+    pub fn set_idle_input(&mut self, value: Option<i64>) {
+        self.idle_input = value;
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    // Queue an input for a machine
+    pub fn send(&mut self, machine: usize, value: i64) {
+        self.machines[machine].provide_input(value);
+    }
+
+    // Deliver a packet to its machine, returns false if there's no such machine
+    pub fn send_packet(&mut self, packet: Packet) -> bool {
+        if packet.dest < 0 || packet.dest as usize >= self.machines.len() {
+            return false;
+        }
+        let machine = &mut self.machines[packet.dest as usize];
+        machine.provide_input(packet.x);
+        machine.provide_input(packet.y);
+        true
+    }
+
+    // Every machine is halted or has nothing queued
+    pub fn is_idle(&self) -> bool {
+        self.machines
+            .iter()
+            .zip(&self.halted)
+            .all(|(machine, halted)| *halted || machine.queued_input() == 0)
+    }
+
+    pub fn run(&mut self) -> Result<NetworkStop, IntcodeError> {
+        self.run_with(&mut ())
+    }
+
+    /// Run the machines in turn until they all halt, the network goes idle and the
+    /// monitor has nothing to wake it with, or the monitor stops it
+    pub fn run_with<M: Monitor>(&mut self, monitor: &mut M) -> Result<NetworkStop, IntcodeError> {
+        // Halted machines are found again, they may have been reset since
+        self.halted = vec![false; self.machines.len()];
+        loop {
+            let mut sent = false;
+            for i in 0..self.machines.len() {
+                if self.halted[i] {
+                    continue;
+                }
+                let mut idle_given = false;
+                loop {
+                    match self.machines[i].run_until_event()? {
+                        Event::Output(value) => {
+                            sent = true;
+                            if !self.route(i, value, monitor) {
+                                return Ok(NetworkStop::Stopped);
+                            }
+                        }
+                        Event::NeedsInput => match self.idle_input {
+                            Some(value) if !idle_given => {
+                                idle_given = true;
+                                self.machines[i].provide_input(value);
+                            }
+                            _ => break,
+                        },
+                        Event::Halted => {
+                            self.halted[i] = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if self.halted.iter().all(|h| *h) {
+                return Ok(NetworkStop::Halted);
+            }
+            if !sent && self.is_idle() {
+                match monitor.on_idle() {
+                    // A packet for nobody can't wake anything either
+                    Some(packet) if self.send_packet(packet) => (),
+                    _ => return Ok(NetworkStop::Idle),
+                }
+            }
+        }
+    }
+
+    // Deliver an output of machine `from`, false if the monitor wants to stop
+    fn route<M: Monitor>(&mut self, from: usize, value: i64, monitor: &mut M) -> bool {
+        let len = self.machines.len();
+        match self.topology {
+            Topology::Chain if from + 1 == len => self.machines[from].output.push(value),
+            Topology::Chain | Topology::Ring => self.send((from + 1) % len, value),
+            Topology::Star if from == 0 => {
+                for machine in &mut self.machines[1..] {
+                    machine.provide_input(value);
+                }
+            }
+            Topology::Star => self.send(0, value),
+            Topology::Mesh => {
+                for (i, machine) in self.machines.iter_mut().enumerate() {
+                    if i != from {
+                        machine.provide_input(value);
+                    }
+                }
+            }
+            Topology::Packets => {
+                self.partial[from].push(value);
+                if self.partial[from].len() == 3 {
+                    let packet = Packet {
+                        dest: self.partial[from][0],
+                        x: self.partial[from][1],
+                        y: self.partial[from][2],
+                    };
+                    self.partial[from].clear();
+                    if !self.send_packet(packet) {
+                        return monitor.on_packet(packet);
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // Adds its first input to every input after it and passes the sum on, halting
+    // once the sum reaches 50
+    const ADDER: &str = "
+            IN   [inc]
+    loop:   IN   [v]
+            ADD  [v], [inc], [v]
+            OUT  [v]
+            LT   [v], #50, [go]
+            JT   [go], #loop
+            HLT
+    inc:    db   0
+    v:      db   0
+    go:     db   0
+    ";
+
+    fn adders(topology: Topology) -> Network {
+        let mut network = Network::new(&assemble(ADDER).unwrap(), 3, topology);
+        for i in 0..3 {
+            network.send(i, i as i64 + 1);
+        }
+        network.send(0, 0);
+        network
+    }
+
+    #[test]
+    fn chain_and_ring() {
+        let mut network = adders(Topology::Chain);
+        assert_eq!(network.run(), Ok(NetworkStop::Idle));
+        assert_eq!(network.machines[2].output, [6]);
+
+        // Each lap adds 6 until machine 1 passes 50 and halts, the others halt one step
+        // behind it
+        let mut network = adders(Topology::Ring);
+        assert_eq!(network.run(), Ok(NetworkStop::Halted));
+        let last: Vec<_> = network.machines.iter().map(|m| m.last_output).collect();
+        assert_eq!(last, [Some(55), Some(51), Some(54)]);
+        assert!(network
+            .machines
+            .iter()
+            .all(|machine| machine.output.is_empty()));
+    }
+
+    #[test]
+    fn star_and_mesh() {
+        // Outputs its one input plus 10
+        let program = assemble("IN [x]\nADD #10, [x], [x]\nOUT [x]\nHLT\nx: db 0").unwrap();
+        // The hub's output goes to both others, theirs back to the hub, which has halted
+        let mut network = Network::new(&program, 3, Topology::Star);
+        network.send(0, 1);
+        assert_eq!(network.run(), Ok(NetworkStop::Halted));
+        assert_eq!(network.machines[0].queued_input(), 2);
+
+        let mut network = Network::new(&program, 3, Topology::Mesh);
+        network.send(1, 5);
+        assert_eq!(network.run(), Ok(NetworkStop::Halted));
+        let last: Vec<_> = network.machines.iter().map(|m| m.last_output).collect();
+        assert_eq!(last, [Some(25), Some(15), Some(25)]);
+    }
+
+    #[derive(Default)]
+    struct Nat {
+        packets: Vec<Packet>,
+        wake: Vec<Packet>,
+        idles: usize,
+        stop_on_packet: bool,
+    }
+
+    impl Monitor for Nat {
+        fn on_packet(&mut self, packet: Packet) -> bool {
+            self.packets.push(packet);
+            !self.stop_on_packet
+        }
+
+        fn on_idle(&mut self) -> Option<Packet> {
+            self.idles += 1;
+            self.wake.pop()
+        }
+    }
+
+    fn machine(source: &str) -> Machine {
+        SuperComputer::standalone("test".to_string(), assemble(source).unwrap())
+    }
+
+    #[test]
+    fn packets_for_nobody_go_to_the_monitor() {
+        let sender = machine("OUT #1\nOUT #7\nOUT #8\nOUT #9\nOUT #10\nOUT #11\nHLT");
+        let receiver = machine("IN [10]\nIN [11]\nHLT");
+        let mut network = Network::from_machines(vec![sender, receiver], Topology::Packets);
+        let mut nat = Nat::default();
+        assert_eq!(network.run_with(&mut nat), Ok(NetworkStop::Halted));
+        let packet = Packet {
+            dest: 9,
+            x: 10,
+            y: 11,
+        };
+        assert_eq!(nat.packets, [packet]);
+        assert_eq!(network.machines[1].digits.range(10, 2), [7, 8]);
+
+        network.machines[0].reset();
+        network.machines[1].reset();
+        nat.stop_on_packet = true;
+        assert_eq!(network.run_with(&mut nat), Ok(NetworkStop::Stopped));
+    }
+
+    #[test]
+    fn idle_until_the_monitor_runs_out() {
+        // Day 23 style, -1 means nothing arrived. Anything else goes to address 2, which
+        // is off the network, twice over.
+        let program = assemble(
+            "
+    loop:   IN   [v]
+            EQ   [v], #-1, [t]
+            JT   [t], #loop
+            OUT  #2
+            OUT  [v]
+            OUT  [v]
+            JT   #1, #loop
+    v:      db   0
+    t:      db   0
+    ",
+        )
+        .unwrap();
+        let mut network = Network::new(&program, 2, Topology::Packets);
+        network.set_idle_input(Some(-1));
+        let mut nat = Nat::default();
+        nat.wake.push(Packet {
+            dest: 1,
+            x: 5,
+            y: 6,
+        });
+        assert_eq!(network.run_with(&mut nat), Ok(NetworkStop::Idle));
+        assert_eq!(nat.idles, 2);
+        let sent: Vec<_> = nat.packets.iter().map(|p| (p.dest, p.x, p.y)).collect();
+        assert_eq!(sent, [(2, 5, 5), (2, 6, 6)]);
+    }
+}
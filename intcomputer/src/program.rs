@@ -0,0 +1,274 @@
+//! Loading Intcode programs from text.
+//!
+//! ```text
+//! ; comments start with ; or # and run to the end of the line
+//! 1101, 2, 3, 7     # numbers are separated by commas, whitespace or both
+//! 4, 7,
+//! 99
+//! ```
+//!
+//! A puzzle input with or without its trailing newline is fine as it is. `load` also
+//! reads the binary format in `image`, telling them apart by its magic number.
+
+use crate::image::{Format, IMAGE_MAGIC};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Where and why some program text didn't parse, line and column count from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// An Intcode program, derefs to its cells so it can go anywhere a `&[i64]` can.
+/// Symbols and the entry point only survive the binary format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    cells: Vec<i64>,
+    // Names for addresses, in the order they were added
+    symbols: Vec<(String, usize)>,
+    entry: Option<usize>,
+}
+
+impl Program {
+    pub fn new(cells: Vec<i64>) -> Program {
+        Program {
+            cells,
+            ..Program::default()
+        }
+    }
+
+    /// Read a program file, text or binary. A parse error comes back as an InvalidData
+    /// error wrapping the ParseError.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Program> {
+        Program::from_bytes(&fs::read(path)?)
+    }
+
+    // Text or binary, like load
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Program> {
+        if bytes.starts_with(IMAGE_MAGIC) {
+            return Program::read_binary(bytes);
+        }
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "program is not text"))?;
+        Program::parse(text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: Format) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Text => self.write_text(writer),
+            Format::Binary => self.write_binary(writer, false),
+            Format::Compact => self.write_binary(writer, true),
+        }
+    }
+
+    // Comma separated on one line, the way puzzle inputs come
+    pub fn write_text<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write!(writer, "{}", cell)?;
+        }
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    pub fn parse(text: &str) -> Result<Program, ParseError> {
+        let mut cells = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            parse_line(line, i + 1, &mut cells)?;
+        }
+        Ok(Program::new(cells))
+    }
+
+    /// A copy with `(address, value)` pairs written over it, growing it with zeros
+    /// if an address is past the end, eg `program.patched(&[(1, noun), (2, verb)])`
+    pub fn patched(&self, patches: &[(usize, i64)]) -> Program {
+        let mut cells = self.cells.clone();
+        for (address, value) in patches {
+            if *address >= cells.len() {
+                cells.resize(address + 1, 0);
+            }
+            cells[*address] = *value;
+        }
+        Program {
+            cells,
+            ..self.clone()
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<i64> {
+        self.cells
+    }
+
+    pub fn symbols(&self) -> &[(String, usize)] {
+        &self.symbols
+    }
+
+    pub fn add_symbol(&mut self, name: &str, address: usize) {
+        self.symbols.push((name.to_string(), address));
+    }
+
+    // Names given to an address
+    pub fn symbols_at(&self, address: usize) -> impl Iterator<Item = &str> {
+        self.symbols
+            .iter()
+            .filter(move |(_, at)| *at == address)
+            .map(|(name, _)| name.as_str())
+    }
+
+    // Where execution is meant to start, a SuperComputer always starts at 0
+    pub fn entry(&self) -> Option<usize> {
+        self.entry
+    }
+
+    pub fn set_entry(&mut self, entry: Option<usize>) {
+        self.entry = entry;
+    }
+}
+
+// Add the numbers on one line to cells
+fn parse_line(line: &str, number: usize, cells: &mut Vec<i64>) -> Result<(), ParseError> {
+    let error = |column: usize, message: String| ParseError {
+        line: number,
+        column: column + 1,
+        message,
+    };
+    let code = match line.find(&[';', '#'][..]) {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+    // A comma has to follow a number, so ",," and a leading comma are errors. Numbers
+    // need a comma or whitespace between them, "12-3" is a typo rather than 12, -3.
+    let mut after_number = false;
+    let mut separated = true;
+    let mut chars = code.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            separated = true;
+            chars.next();
+        } else if c == ',' {
+            if !after_number {
+                return Err(error(start, "expected a number before ','".to_string()));
+            }
+            after_number = false;
+            separated = true;
+            chars.next();
+        } else if c == '-' || c == '+' || c.is_ascii_digit() {
+            if !separated {
+                return Err(error(
+                    start,
+                    format!("expected ',' or whitespace before '{}'", c),
+                ));
+            }
+            let mut end = start + c.len_utf8();
+            chars.next();
+            while let Some(&(i, c)) = chars.peek() {
+                if !c.is_ascii_alphanumeric() {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let word = &code[start..end];
+            let value = word.parse::<i64>().map_err(|_| {
+                let message = match word.trim_start_matches(&['-', '+'][..]) {
+                    digits if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                        format!("{} doesn't fit in an i64", word)
+                    }
+                    _ => format!("{} isn't a number", word),
+                };
+                error(start, message)
+            })?;
+            cells.push(value);
+            after_number = true;
+            separated = false;
+        } else {
+            return Err(error(start, format!("unexpected '{}'", c)));
+        }
+    }
+    Ok(())
+}
+
+impl Deref for Program {
+    type Target = [i64];
+    fn deref(&self) -> &[i64] {
+        &self.cells
+    }
+}
+
+impl From<Vec<i64>> for Program {
+    fn from(cells: Vec<i64>) -> Program {
+        Program::new(cells)
+    }
+}
+
+impl From<Program> for Vec<i64> {
+    fn from(program: Program) -> Vec<i64> {
+        program.cells
+    }
+}
+
+// So a Program can be handed straight to SuperComputer::new
+impl From<Program> for Arc<[i64]> {
+    fn from(program: Program) -> Arc<[i64]> {
+        program.cells.into()
+    }
+}
+
+impl From<&Program> for Arc<[i64]> {
+    fn from(program: &Program) -> Arc<[i64]> {
+        program.cells[..].into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> (usize, usize, String) {
+        let error = Program::parse(text).unwrap_err();
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn commas_whitespace_and_comments() {
+        let program = Program::parse("1,-2, +3\n4 5 ; five\n\n# a comment\n6,\n").unwrap();
+        assert_eq!(&program[..], [1, -2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn numbers_need_a_separator() {
+        assert_eq!(
+            error("1,2\n12-3"),
+            (2, 3, "expected ',' or whitespace before '-'".to_string())
+        );
+        assert_eq!(
+            error("1+2"),
+            (1, 2, "expected ',' or whitespace before '+'".to_string())
+        );
+        assert_eq!(error("1,,2").1, 3);
+        assert_eq!(error("12x").2, "12x isn't a number");
+        assert_eq!(
+            error("9223372036854775808").2,
+            "9223372036854775808 doesn't fit in an i64"
+        );
+    }
+}
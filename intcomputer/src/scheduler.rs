@@ -0,0 +1,277 @@
+//! Runs any number of computers on the calling thread, taking turns.
+//!
+//! ```no_run
+//! # use intcomputer::*;
+//! # use std::sync::mpsc::channel;
+//! # fn main() -> Result<(), IntcodeError> {
+//! # let a_digits = Program::load("a.txt").unwrap();
+//! # let b_digits = Program::load("b.txt").unwrap();
+//! let (a_send, b_recv) = channel();
+//! let (b_send, a_recv) = channel();
+//! let mut scheduler = Scheduler::new(1000);
+//! scheduler.add(SuperComputer::new("A".to_string(), a_digits, a_send, a_recv));
+//! scheduler.add(SuperComputer::new("B".to_string(), b_digits, b_send, b_recv));
+//! match scheduler.run()? {
+//!     Outcome::Finished => (),
+//!     Outcome::Deadlock(report) => println!("{}", report),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each turn a machine runs until it has executed its time slice, halts, or needs input
+//! its input doesn't have yet (`IntcodeInput::try_read` returning Empty). Then the next
+//! machine goes. Channels work fine as input and output since nothing ever waits on
+//! them, and the order things happen in is the same every run.
+
+use crate::{Event, HaltReason, IntcodeError, IntcodeInput, IntcodeOutput, SuperComputer, TryRead};
+use std::fmt;
+use std::sync::mpsc::{Receiver, Sender};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TaskState {
+    // Gets a turn next round
+    Ready,
+    // Waiting on input, tries again next round
+    Blocked,
+    // Done for good, the reason is what run would have returned
+    Finished(HaltReason),
+}
+
+/// A machine stuck waiting on input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blocked {
+    pub machine: usize,
+    pub name: String,
+    pub sp: usize,
+    pub instructions: u64,
+}
+
+/// Every machine that hasn't finished is waiting on input nobody will send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    pub blocked: Vec<Blocked>,
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "deadlock, every running machine is waiting on input:")?;
+        for blocked in &self.blocked {
+            writeln!(
+                f,
+                "  {} ({}) at {:04} after {} instructions",
+                blocked.machine, blocked.name, blocked.sp, blocked.instructions
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Why `Scheduler::run` returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    // Every machine finished
+    Finished,
+    Deadlock(Deadlock),
+}
+
+pub struct Scheduler<I = Receiver<i64>, O = Sender<i64>> {
+    pub machines: Vec<SuperComputer<I, O>>,
+    states: Vec<TaskState>,
+    // Instructions a machine may execute per turn
+    slice: u64,
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Scheduler<I, O> {
+    pub fn new(slice: u64) -> Scheduler<I, O> {
+        Scheduler {
+            machines: Vec::new(),
+            states: Vec::new(),
+            slice: slice.max(1),
+        }
+    }
+
+    // Returns the index of the machine
+    pub fn add(&mut self, machine: SuperComputer<I, O>) -> usize {
+        self.machines.push(machine);
+        self.states.push(TaskState::Ready);
+        self.machines.len() - 1
+    }
+
+    pub fn slice(&self) -> u64 {
+        self.slice
+    }
+
+    pub fn set_slice(&mut self, slice: u64) {
+        self.slice = slice.max(1);
+    }
+
+    pub fn state(&self, machine: usize) -> TaskState {
+        self.states[machine]
+    }
+
+    /// Give every machine that hasn't finished a turn. Returns whether any of them got
+    /// anything done.
+    pub fn round(&mut self) -> Result<bool, IntcodeError> {
+        let mut progress = false;
+        for i in 0..self.machines.len() {
+            if let TaskState::Finished(_) = self.states[i] {
+                continue;
+            }
+            let before = self.machines[i].instructions();
+            self.states[i] = self.turn(i)?;
+            progress |= self.machines[i].instructions() != before;
+        }
+        Ok(progress)
+    }
+
+    /// Take turns until every machine finishes or they are all stuck waiting on input
+    pub fn run(&mut self) -> Result<Outcome, IntcodeError> {
+        loop {
+            let progress = self.round()?;
+            let finished = self
+                .states
+                .iter()
+                .all(|state| matches!(state, TaskState::Finished(_)));
+            if finished {
+                return Ok(Outcome::Finished);
+            }
+            if !progress {
+                return Ok(Outcome::Deadlock(self.deadlock()));
+            }
+        }
+    }
+
+    fn turn(&mut self, i: usize) -> Result<TaskState, IntcodeError> {
+        let machine = &mut self.machines[i];
+        let start = machine.instructions();
+        while machine.instructions() - start < self.slice {
+            match machine.step()? {
+                None => (),
+                Some(Event::Output(output)) => {
+                    let open = machine.output.write(output);
+                    if !open {
+                        return Ok(TaskState::Finished(HaltReason::OutputClosed));
+                    }
+                }
+                Some(Event::NeedsInput) => match machine.input.try_read() {
+                    TryRead::Value(input) => machine.provide_input(input),
+                    TryRead::Empty => return Ok(TaskState::Blocked),
+                    TryRead::Closed => return Ok(TaskState::Finished(HaltReason::InputClosed)),
+                },
+                Some(Event::Halted) => return Ok(TaskState::Finished(HaltReason::Halted)),
+            }
+        }
+        Ok(TaskState::Ready)
+    }
+
+    fn deadlock(&self) -> Deadlock {
+        let blocked = self
+            .states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| **state == TaskState::Blocked)
+            .map(|(i, _)| {
+                let machine = &self.machines[i];
+                Blocked {
+                    machine: i,
+                    name: machine.name.clone(),
+                    sp: machine.sp(),
+                    instructions: machine.instructions(),
+                }
+            })
+            .collect();
+        Deadlock { blocked }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+    use std::sync::mpsc::channel;
+
+    // Two machines feeding each other through channels, `first` goes to machine 0
+    fn pair(a: &str, b: &str, first: Option<i64>, slice: u64) -> Scheduler {
+        let (to_a, a_input) = channel();
+        let (to_b, b_input) = channel();
+        if let Some(value) = first {
+            to_a.send(value).unwrap();
+        }
+        let mut scheduler = Scheduler::new(slice);
+        scheduler.add(SuperComputer::new(
+            "a".to_string(),
+            assemble(a).unwrap(),
+            to_b,
+            a_input,
+        ));
+        scheduler.add(SuperComputer::new(
+            "b".to_string(),
+            assemble(b).unwrap(),
+            to_a,
+            b_input,
+        ));
+        scheduler
+    }
+
+    // Applies `op` to every input and passes it on until the result reaches 1000
+    fn looping(op: &str) -> String {
+        format!(
+            "
+    loop:   IN   [x]
+            {}
+            OUT  [x]
+            LT   [x], #1000, [go]
+            JT   [go], #loop
+            HLT
+    x:      db   0
+    go:     db   0
+    ",
+            op
+        )
+    }
+
+    #[test]
+    fn slice_size_does_not_change_the_result() {
+        let double = looping("ADD [x], [x], [x]");
+        let increment = looping("ADD [x], #1, [x]");
+        let mut results = Vec::new();
+        for slice in [1, 1000].iter() {
+            let mut scheduler = pair(&double, &increment, Some(1), *slice);
+            assert_eq!(scheduler.run(), Ok(Outcome::Finished));
+            let machines = &scheduler.machines;
+            results.push((
+                machines[0].last_output,
+                machines[1].last_output,
+                machines[0].instructions(),
+                machines[1].instructions(),
+            ));
+            assert_eq!(scheduler.state(0), TaskState::Finished(HaltReason::Halted));
+        }
+        assert_eq!(results[0], results[1]);
+        assert_eq!((results[0].0, results[0].1), (Some(1022), Some(1023)));
+    }
+
+    #[test]
+    fn both_waiting_is_a_deadlock() {
+        // Machine 0 gets one instruction in before waiting, machine 1 none
+        let mut scheduler = pair("ADD #1, #1, [x]\nIN [x]\nx: db 0", "IN [0]", None, 10);
+        let blocked = |machine: usize, name: &str, sp: usize, instructions: u64| Blocked {
+            machine,
+            name: name.to_string(),
+            sp,
+            instructions,
+        };
+        let deadlock = Deadlock {
+            blocked: vec![blocked(0, "a", 4, 1), blocked(1, "b", 0, 0)],
+        };
+        assert_eq!(scheduler.run(), Ok(Outcome::Deadlock(deadlock.clone())));
+        assert_eq!(scheduler.state(1), TaskState::Blocked);
+        assert_eq!(
+            deadlock.to_string(),
+            "deadlock, every running machine is waiting on input:\n  \
+             0 (a) at 0004 after 1 instructions\n  \
+             1 (b) at 0000 after 0 instructions\n"
+        );
+    }
+}
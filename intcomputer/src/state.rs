@@ -0,0 +1,256 @@
+//! Save a `SuperComputer` to disk and resume it later.
+//!
+//! The format is little endian binary, version 1:
+//!
+//! | field        | encoding                                                    |
+//! |--------------|-------------------------------------------------------------|
+//! | magic        | the 4 bytes `ICMS`                                          |
+//! | version      | u32, currently 1                                            |
+//! | name         | u32 byte length then UTF-8 bytes                            |
+//! | sp           | u64                                                         |
+//! | rb           | i64                                                         |
+//! | memory limit | u64                                                         |
+//! | last output  | u8, 1 if there is one, followed by it as an i64             |
+//! | input queue  | u64 count then each value as an i64                         |
+//! | memory       | u64 count of segments, each a u64 start address, u64 length |
+//! |              | and that many i64 cells. Cells outside every segment are 0. |
+//!
+//! Queued input is only what was given with `provide_input`, anything still sitting in
+//! the computer's input or output stays with them. Controls, the instruction count and
+//! the original program aren't saved, so `reset` on a loaded computer clears memory.
+//! Neither are registered instructions or host functions, a loaded computer has the
+//! standard set.
+
+use crate::{IntcodeInput, IntcodeOutput, Memory, SuperComputer};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"ICMS";
+pub const STATE_VERSION: u32 = 1;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_i64<W: Write>(writer: &mut W, value: i64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_i64<R: Read>(reader: &mut R) -> io::Result<i64> {
+    Ok(read_u64(reader)? as i64)
+}
+
+// A u32 byte length then that many bytes. The length can't be trusted, so it's read as
+// far as the file goes rather than allocated up front.
+pub(crate) fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        ));
+    }
+    Ok(bytes)
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> SuperComputer<I, O> {
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&STATE_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.name.len() as u32).to_le_bytes())?;
+        writer.write_all(self.name.as_bytes())?;
+        write_u64(&mut writer, self.sp as u64)?;
+        write_i64(&mut writer, self.rb)?;
+        write_u64(&mut writer, self.digits.limit() as u64)?;
+        match self.last_output {
+            Some(output) => {
+                writer.write_all(&[1])?;
+                write_i64(&mut writer, output)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        write_u64(&mut writer, self.input_queue.len() as u64)?;
+        for input in &self.input_queue {
+            write_i64(&mut writer, *input)?;
+        }
+        // One segment per page, with the zeros on the end trimmed off
+        let pages: Vec<(usize, &[i64])> = self
+            .digits
+            .pages()
+            .into_iter()
+            .map(|(start, cells)| {
+                let len = cells
+                    .iter()
+                    .rposition(|cell| *cell != 0)
+                    .map_or(0, |i| i + 1);
+                (start, &cells[..len])
+            })
+            .filter(|(_, cells)| !cells.is_empty())
+            .collect();
+        write_u64(&mut writer, pages.len() as u64)?;
+        for (start, cells) in pages {
+            write_u64(&mut writer, start as u64)?;
+            write_u64(&mut writer, cells.len() as u64)?;
+            for cell in cells {
+                write_i64(&mut writer, *cell)?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Resume a computer written by `save`, attached to a new output and input
+    pub fn load<R: Read>(mut reader: R, output: O, input: I) -> io::Result<SuperComputer<I, O>> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a saved intcode computer".to_string()));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != STATE_VERSION {
+            return Err(invalid(format!("unsupported state version {}", version)));
+        }
+        let name = String::from_utf8(read_bytes(&mut reader)?)
+            .map_err(|_| invalid("name is not UTF-8".to_string()))?;
+        let sp = read_u64(&mut reader)? as usize;
+        let rb = read_i64(&mut reader)?;
+        let limit = read_u64(&mut reader)? as usize;
+        let mut flag = [0; 1];
+        reader.read_exact(&mut flag)?;
+        let last_output = match flag[0] {
+            0 => None,
+            1 => Some(read_i64(&mut reader)?),
+            flag => return Err(invalid(format!("bad last output flag {}", flag))),
+        };
+        let mut input_queue = VecDeque::new();
+        for _ in 0..read_u64(&mut reader)? {
+            input_queue.push_back(read_i64(&mut reader)?);
+        }
+        let mut digits = Memory::with_limit(&[], limit);
+        for _ in 0..read_u64(&mut reader)? {
+            let start = read_u64(&mut reader)? as usize;
+            let len = read_u64(&mut reader)? as usize;
+            match start.checked_add(len) {
+                Some(end) if end <= limit => (),
+                _ => {
+                    return Err(invalid(format!(
+                        "segment at {} is past the memory limit",
+                        start
+                    )))
+                }
+            }
+            for address in start..start + len {
+                digits.set(address, read_i64(&mut reader)?);
+            }
+        }
+        Ok(SuperComputer {
+            name,
+            image: Arc::from(Vec::new()),
+            digits,
+            sp,
+            rb,
+            output,
+            input,
+            input_queue,
+            control: None,
+            paused: false,
+            instructions: 0,
+            cache: Default::default(),
+            isa: Default::default(),
+            hosts: Default::default(),
+            last_output,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+
+    // Reads two numbers into far apart cells and outputs their sum
+    const ADD: [i64; 11] = [3, 5000, 3, 70000, 1, 5000, 70000, 0, 4, 0, 99];
+
+    #[test]
+    fn loaded_computer_carries_on() {
+        let mut computer = SuperComputer::standalone("adder".to_string(), ADD.to_vec());
+        computer.digits.set_limit(100000);
+        computer.provide_input(30);
+        assert_eq!(computer.run_until_event(), Ok(Event::NeedsInput));
+        computer.provide_input(12);
+        computer.provide_input(99);
+
+        let mut saved = Vec::new();
+        computer.save(&mut saved).unwrap();
+        let mut loaded = SuperComputer::load(&saved[..], Vec::new(), VecDeque::new()).unwrap();
+        assert_eq!(loaded.name, "adder");
+        assert_eq!((loaded.sp(), loaded.rb()), (computer.sp(), computer.rb()));
+        assert_eq!(loaded.digits.limit(), 100000);
+        assert_eq!(loaded.queued_input(), 2);
+        assert_eq!(loaded.digits.get(5000), 30);
+
+        assert_eq!(loaded.run_until_event(), Ok(Event::Output(42)));
+        assert_eq!(loaded.last_output, Some(42));
+        let mut resaved = Vec::new();
+        loaded.save(&mut resaved).unwrap();
+        let reloaded = SuperComputer::load(&resaved[..], Vec::new(), VecDeque::new()).unwrap();
+        assert_eq!(reloaded.last_output, Some(42));
+        assert_eq!(reloaded.queued_input(), 1);
+        assert_eq!(
+            reloaded.digits.range(0, ADD.len()),
+            loaded.digits.range(0, ADD.len())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let mut computer = SuperComputer::standalone("adder".to_string(), ADD.to_vec());
+        let mut saved = Vec::new();
+        computer.save(&mut saved).unwrap();
+        let load = |bytes: &[u8]| {
+            SuperComputer::<VecDeque<i64>, Vec<i64>>::load(bytes, Vec::new(), VecDeque::new())
+                .err()
+                .map(|err| err.kind())
+        };
+        assert_eq!(load(&saved), None);
+
+        let mut bad = saved.clone();
+        bad[0] = b'X';
+        assert_eq!(load(&bad), Some(io::ErrorKind::InvalidData));
+        let mut bad = saved.clone();
+        bad[4] = 2;
+        assert_eq!(load(&bad), Some(io::ErrorKind::InvalidData));
+        assert_eq!(
+            load(&saved[..saved.len() - 1]),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+        // A name length of 4GB is only read as far as the file goes
+        let mut bad = saved.clone();
+        bad[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(load(&bad), Some(io::ErrorKind::UnexpectedEof));
+
+        // A segment reaching past the limit
+        computer.digits.set_limit(8);
+        let mut saved = Vec::new();
+        computer.save(&mut saved).unwrap();
+        assert_eq!(load(&saved), Some(io::ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,653 @@
+//! Running a program with some cells left as unknowns.
+//!
+//! ```no_run
+//! # use intcomputer::symbolic::SymbolicComputer;
+//! # use intcomputer::Program;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let program = Program::load("input.txt")?;
+//! let mut computer = SymbolicComputer::new(&program);
+//! computer.symbol(1, "noun");
+//! computer.symbol(2, "verb");
+//! computer.run()?;
+//! let result = computer.get(0).linear().unwrap();       // 1114711 + 216000*noun + verb
+//! let answer = result.solve(19690720, &[("noun", 0..100), ("verb", 0..100)]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Add, Mul, LessThan and Equals on unknowns build expression trees instead of numbers,
+//! everything concrete runs like it would on a SuperComputer. Reading through an
+//! unknown address gives a `Read` of it. Anything that decides where execution or a
+//! write goes, an op code, a jump condition or target, a write address or an rb
+//! adjustment, has to come out concrete, otherwise the run stops with NotConcrete.
+
+use crate::{FaultKind, HaltReason, OpCode, ParameterMode, DEFAULT_MEMORY_LIMIT};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Mul, Range};
+use std::sync::Arc;
+
+/// A value that is a number once the unknowns in it are
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i64),
+    Var(Arc<str>),
+    Add(Arc<Expr>, Arc<Expr>),
+    Mul(Arc<Expr>, Arc<Expr>),
+    // 1 if less than, 0 if not
+    Lt(Arc<Expr>, Arc<Expr>),
+    // 1 if equal, 0 if not
+    Eq(Arc<Expr>, Arc<Expr>),
+    // The cell at an unknown address, as it was when read
+    Read(Arc<Expr>),
+}
+
+impl Expr {
+    pub fn var(name: &str) -> Expr {
+        Expr::Var(name.into())
+    }
+
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    // Whether it has a Read in it. Two of the same Read can differ, the cell may have
+    // been written between them.
+    pub fn reads_memory(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => false,
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::Lt(a, b) | Expr::Eq(a, b) => {
+                a.reads_memory() || b.reads_memory()
+            }
+            Expr::Read(_) => true,
+        }
+    }
+
+    pub fn less_than(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as i64),
+            (x, y) if x == y && !x.reads_memory() => Expr::Const(0),
+            (x, y) => Expr::Lt(Arc::new(x), Arc::new(y)),
+        }
+    }
+
+    pub fn equals(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as i64),
+            (x, y) if x == y && !x.reads_memory() => Expr::Const(1),
+            (x, y) => Expr::Eq(Arc::new(x), Arc::new(y)),
+        }
+    }
+
+    /// The value with numbers for its unknowns, None if one is missing, it has a Read
+    /// or it overflows
+    pub fn eval(&self, vars: &[(&str, i64)]) -> Option<i64> {
+        Some(match self {
+            Expr::Const(value) => *value,
+            Expr::Var(name) => vars.iter().find(|(var, _)| *var == &**name)?.1,
+            Expr::Add(a, b) => a.eval(vars)?.checked_add(b.eval(vars)?)?,
+            Expr::Mul(a, b) => a.eval(vars)?.checked_mul(b.eval(vars)?)?,
+            Expr::Lt(a, b) => (a.eval(vars)? < b.eval(vars)?) as i64,
+            Expr::Eq(a, b) => (a.eval(vars)? == b.eval(vars)?) as i64,
+            Expr::Read(_) => return None,
+        })
+    }
+
+    /// The expression as a sum of unknowns times numbers, None if it isn't one or a
+    /// number in it overflows
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(value) => Some(Linear {
+                constant: *value,
+                terms: BTreeMap::new(),
+            }),
+            Expr::Var(name) => {
+                let mut terms = BTreeMap::new();
+                terms.insert(name.to_string(), 1);
+                Some(Linear { constant: 0, terms })
+            }
+            Expr::Add(a, b) => {
+                let (mut a, b) = (a.linear()?, b.linear()?);
+                a.constant = a.constant.checked_add(b.constant)?;
+                for (name, coefficient) in b.terms {
+                    let sum = a.terms.entry(name).or_insert(0);
+                    *sum = sum.checked_add(coefficient)?;
+                }
+                a.terms.retain(|_, coefficient| *coefficient != 0);
+                Some(a)
+            }
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                // Only linear if one side is just a number
+                let (mut x, factor) = match (a.terms.is_empty(), b.terms.is_empty()) {
+                    (_, true) => (a, b.constant),
+                    (true, false) => (b, a.constant),
+                    (false, false) => return None,
+                };
+                x.constant = x.constant.checked_mul(factor)?;
+                for coefficient in x.terms.values_mut() {
+                    *coefficient = coefficient.checked_mul(factor)?;
+                }
+                x.terms.retain(|_, coefficient| *coefficient != 0);
+                Some(x)
+            }
+            Expr::Lt(..) | Expr::Eq(..) | Expr::Read(_) => None,
+        }
+    }
+
+    /// See `Linear::solve`, None if the expression isn't linear either
+    pub fn solve(&self, target: i64, ranges: &[(&str, Range<i64>)]) -> Option<Vec<(String, i64)>> {
+        self.linear()?.solve(target, ranges)
+    }
+}
+
+// Constants are folded and kept on the right, so a chain of adds or muls with numbers
+// in it ends with a single number. Constants that would overflow are left unfolded,
+// eval and linear give None for them.
+impl Add for Expr {
+    type Output = Expr;
+    fn add(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) if a.checked_add(b).is_some() => Expr::Const(a + b),
+            (Expr::Const(0), x) | (x, Expr::Const(0)) => x,
+            (Expr::Const(a), x) | (x, Expr::Const(a)) => match x {
+                Expr::Add(x, b) if b.as_const().and_then(|b| b.checked_add(a)).is_some() => {
+                    Expr::Add(x, Arc::new(Expr::Const(a + b.as_const().unwrap())))
+                }
+                x => Expr::Add(Arc::new(x), Arc::new(Expr::Const(a))),
+            },
+            (x, y) => Expr::Add(Arc::new(x), Arc::new(y)),
+        }
+    }
+}
+
+impl Mul for Expr {
+    type Output = Expr;
+    fn mul(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(a), Expr::Const(b)) if a.checked_mul(b).is_some() => Expr::Const(a * b),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), x) | (x, Expr::Const(1)) => x,
+            (Expr::Const(a), x) | (x, Expr::Const(a)) => match x {
+                Expr::Mul(x, b) if b.as_const().and_then(|b| b.checked_mul(a)).is_some() => {
+                    Expr::Mul(x, Arc::new(Expr::Const(a * b.as_const().unwrap())))
+                }
+                x => Expr::Mul(Arc::new(x), Arc::new(Expr::Const(a))),
+            },
+            (x, y) => Expr::Mul(Arc::new(x), Arc::new(y)),
+        }
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Expr {
+        Expr::Const(value)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Brackets around anything that isn't a single number or name
+        fn operand(f: &mut fmt::Formatter, expr: &Expr) -> fmt::Result {
+            match expr {
+                Expr::Const(_) | Expr::Var(_) | Expr::Read(_) => write!(f, "{}", expr),
+                _ => write!(f, "({})", expr),
+            }
+        }
+        let (a, op, b) = match self {
+            Expr::Const(value) => return write!(f, "{}", value),
+            Expr::Var(name) => return write!(f, "{}", name),
+            Expr::Read(address) => return write!(f, "[{}]", address),
+            Expr::Add(a, b) => (a, "+", b),
+            Expr::Mul(a, b) => (a, "*", b),
+            Expr::Lt(a, b) => (a, "<", b),
+            Expr::Eq(a, b) => (a, "==", b),
+        };
+        operand(f, a)?;
+        write!(f, " {} ", op)?;
+        operand(f, b)
+    }
+}
+
+/// `constant + coefficient * name + ...`, with no zero coefficients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i64,
+    pub terms: BTreeMap<String, i64>,
+}
+
+impl Linear {
+    /// Values for the unknowns making this equal target, the first found trying each
+    /// one over its range in the order given. At most one unknown can be left out of
+    /// ranges; it, or else the last one in ranges, is worked out by dividing rather
+    /// than searched for, so two unknowns over 0..100 take 100 tries, not 10000.
+    /// Unknowns not in the expression get the start of their range. Values whose terms
+    /// overflow an i64 along the way are never a solution.
+    pub fn solve(&self, target: i64, ranges: &[(&str, Range<i64>)]) -> Option<Vec<(String, i64)>> {
+        let mut unranged = self
+            .terms
+            .keys()
+            .filter(|name| ranges.iter().all(|(var, _)| var != name));
+        let free = match (unranged.next(), unranged.next()) {
+            (Some(name), None) => Some((name.as_str(), None)),
+            (Some(_), Some(_)) => return None,
+            (None, _) => ranges
+                .iter()
+                .rev()
+                .find(|(name, _)| self.terms.contains_key(*name))
+                .map(|(name, range)| (*name, Some(range.clone()))),
+        };
+        let searched: Vec<(&str, i64, Range<i64>)> = ranges
+            .iter()
+            .filter(|(name, _)| free.as_ref().is_none_or(|(free, _)| free != name))
+            .map(|(name, range)| (*name, self.coefficient(name), range.clone()))
+            .collect();
+
+        let mut values = Vec::new();
+        let rest = target.checked_sub(self.constant)?;
+        if !self.search(rest, &searched, &free, &mut values) {
+            return None;
+        }
+        // The free unknown's value was pushed last, put it back where it was asked for
+        let free_value = free.as_ref().and_then(|_| values.pop());
+        let mut solution: Vec<(String, i64)> = searched
+            .iter()
+            .map(|(name, _, _)| name.to_string())
+            .zip(values)
+            .collect();
+        if let (Some((name, _)), Some(value)) = (free, free_value) {
+            let at = ranges
+                .iter()
+                .position(|(var, _)| *var == name)
+                .unwrap_or(solution.len());
+            solution.insert(at, (name.to_string(), value));
+        }
+        Some(solution)
+    }
+
+    pub fn coefficient(&self, name: &str) -> i64 {
+        self.terms.get(name).copied().unwrap_or(0)
+    }
+
+    // Try each value of the first searched unknown and recurse, solving the free one
+    // for what is left once they all have values
+    fn search(
+        &self,
+        rest: i64,
+        searched: &[(&str, i64, Range<i64>)],
+        free: &Option<(&str, Option<Range<i64>>)>,
+        values: &mut Vec<i64>,
+    ) -> bool {
+        let (coefficient, range) = match searched.split_first() {
+            Some(((_, 0, range), more)) => {
+                values.push(range.start);
+                if !range.is_empty() && self.search(rest, more, free, values) {
+                    return true;
+                }
+                values.pop();
+                return false;
+            }
+            Some(((_, coefficient, range), _)) => (*coefficient, range.clone()),
+            None => {
+                return match free {
+                    None => rest == 0,
+                    Some((name, range)) => {
+                        let coefficient = self.coefficient(name);
+                        match (rest.checked_div(coefficient), rest.checked_rem(coefficient)) {
+                            (Some(value), Some(0))
+                                if range.as_ref().is_none_or(|range| range.contains(&value)) =>
+                            {
+                                values.push(value);
+                                true
+                            }
+                            _ => false,
+                        }
+                    }
+                };
+            }
+        };
+        for value in range {
+            let rest = match coefficient
+                .checked_mul(value)
+                .and_then(|term| rest.checked_sub(term))
+            {
+                Some(rest) => rest,
+                None => continue,
+            };
+            values.push(value);
+            if self.search(rest, &searched[1..], free, values) {
+                return true;
+            }
+            values.pop();
+        }
+        false
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.constant)?;
+        for (name, coefficient) in &self.terms {
+            match *coefficient {
+                1 => write!(f, " + {}", name)?,
+                -1 => write!(f, " - {}", name)?,
+                c if c < 0 => write!(f, " - {}*{}", -c, name)?,
+                c => write!(f, " + {}*{}", c, name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a symbolic run stopped without halting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    // The same faults a SuperComputer raises
+    Fault { ip: usize, kind: FaultKind },
+    // Something that has to be a number to carry on depends on an unknown
+    NotConcrete { ip: usize, expr: Expr },
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Fault { ip, kind } => write!(f, "{} at ip {}", kind, ip),
+            SymbolicError::NotConcrete { ip, expr } => {
+                write!(f, "{} needs to be a number at ip {}", expr, ip)
+            }
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+/// An Intcode machine whose memory, input and output hold expressions
+#[derive(Debug, Clone)]
+pub struct SymbolicComputer {
+    // The program's cells
+    memory: Vec<Expr>,
+    // Cells past the program that have been written, addresses go up to the memory
+    // limit so they aren't kept in one Vec
+    beyond: HashMap<usize, Expr>,
+    sp: usize,
+    rb: i64,
+    input: VecDeque<Expr>,
+    pub output: Vec<Expr>,
+}
+
+impl SymbolicComputer {
+    pub fn new(program: &[i64]) -> SymbolicComputer {
+        SymbolicComputer {
+            memory: program.iter().map(|cell| Expr::Const(*cell)).collect(),
+            beyond: HashMap::new(),
+            sp: 0,
+            rb: 0,
+            input: VecDeque::new(),
+            output: Vec::new(),
+        }
+    }
+
+    // Make a cell an unknown called name
+    pub fn symbol(&mut self, address: usize, name: &str) {
+        self.set(address, Expr::var(name));
+    }
+
+    pub fn get(&self, address: usize) -> Expr {
+        match self.memory.get(address) {
+            Some(cell) => cell.clone(),
+            None => self.beyond.get(&address).cloned().unwrap_or(Expr::Const(0)),
+        }
+    }
+
+    pub fn set(&mut self, address: usize, value: impl Into<Expr>) {
+        match self.memory.get_mut(address) {
+            Some(cell) => *cell = value.into(),
+            None => {
+                self.beyond.insert(address, value.into());
+            }
+        }
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    pub fn provide_input(&mut self, input: impl Into<Expr>) {
+        self.input.push_back(input.into());
+    }
+
+    /// Run until a halt, or until an Input with nothing queued, leaving sp on it
+    pub fn run(&mut self) -> Result<HaltReason, SymbolicError> {
+        loop {
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<Option<HaltReason>, SymbolicError> {
+        let ip = self.sp;
+        let instruction = self.concrete(ip, self.get(ip))?;
+        let op_code = OpCode::try_from(instruction % 100).map_err(|kind| self.fault(kind))?;
+        let mut modes = [ParameterMode::Pointer; 3];
+        for (i, mode) in modes.iter_mut().enumerate().take(op_code.num_params()) {
+            let digit = instruction / 10i64.pow(i as u32 + 2) % 10;
+            *mode = ParameterMode::try_from(digit).map_err(|kind| self.fault(kind))?;
+        }
+        let params: Vec<Expr> = (1..=op_code.num_params())
+            .map(|i| self.get(ip + i))
+            .collect();
+
+        let next = ip + 1 + op_code.num_params();
+        match op_code {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => {
+                let a = self.read(&params[0], modes[0])?;
+                let b = self.read(&params[1], modes[1])?;
+                // Concrete numbers fault on overflow like they would on a SuperComputer
+                let value = match (op_code, a.as_const(), b.as_const()) {
+                    (OpCode::Add, Some(x), Some(y)) => Expr::Const(self.checked(x.checked_add(y))?),
+                    (OpCode::Mul, Some(x), Some(y)) => Expr::Const(self.checked(x.checked_mul(y))?),
+                    (OpCode::Add, ..) => a + b,
+                    (OpCode::Mul, ..) => a * b,
+                    (OpCode::LessThan, ..) => a.less_than(b),
+                    _ => a.equals(b),
+                };
+                self.write(&params[2], modes[2], value)?;
+            }
+            OpCode::Input => match self.input.pop_front() {
+                Some(value) => self.write(&params[0], modes[0], value)?,
+                None => return Ok(Some(HaltReason::InputClosed)),
+            },
+            OpCode::Output => {
+                let value = self.read(&params[0], modes[0])?;
+                self.output.push(value);
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let condition = self.read(&params[0], modes[0])?;
+                let condition = self.concrete(ip, condition)?;
+                if (condition != 0) == (op_code == OpCode::JumpIfTrue) {
+                    let target = self.read(&params[1], modes[1])?;
+                    let target = self.concrete(ip, target)?;
+                    self.sp = self.address(target)?;
+                    return Ok(None);
+                }
+            }
+            OpCode::RelativeBase => {
+                let offset = self.read(&params[0], modes[0])?;
+                let offset = self.concrete(ip, offset)?;
+                self.rb = self.checked(self.rb.checked_add(offset))?;
+            }
+            OpCode::Halt => return Ok(Some(HaltReason::Halted)),
+        }
+        self.sp = next;
+        Ok(None)
+    }
+
+    fn read(&self, param: &Expr, mode: ParameterMode) -> Result<Expr, SymbolicError> {
+        let address = match (mode, param) {
+            (ParameterMode::Value, _) => return Ok(param.clone()),
+            (ParameterMode::Pointer, Expr::Const(address)) => *address,
+            (ParameterMode::Relative, Expr::Const(offset)) => {
+                self.checked(self.rb.checked_add(*offset))?
+            }
+            (ParameterMode::Pointer, _) => return Ok(Expr::Read(Arc::new(param.clone()))),
+            (ParameterMode::Relative, _) => {
+                let address = param.clone() + Expr::Const(self.rb);
+                return Ok(Expr::Read(Arc::new(address)));
+            }
+        };
+        Ok(self.get(self.address(address)?))
+    }
+
+    fn write(
+        &mut self,
+        param: &Expr,
+        mode: ParameterMode,
+        value: Expr,
+    ) -> Result<(), SymbolicError> {
+        let param = self.concrete(self.sp, param.clone())?;
+        let address = match mode {
+            ParameterMode::Pointer => param,
+            ParameterMode::Relative => self.checked(self.rb.checked_add(param))?,
+            ParameterMode::Value => return Err(self.fault(FaultKind::ImmediateWrite)),
+        };
+        let address = self.address(address)?;
+        self.set(address, value);
+        Ok(())
+    }
+
+    fn address(&self, address: i64) -> Result<usize, SymbolicError> {
+        if address < 0 {
+            Err(self.fault(FaultKind::NegativeAddress(address)))
+        } else if address as usize >= DEFAULT_MEMORY_LIMIT {
+            Err(self.fault(FaultKind::AddressOutOfRange(address)))
+        } else {
+            Ok(address as usize)
+        }
+    }
+
+    fn concrete(&self, ip: usize, expr: Expr) -> Result<i64, SymbolicError> {
+        match expr {
+            Expr::Const(value) => Ok(value),
+            expr => Err(SymbolicError::NotConcrete { ip, expr }),
+        }
+    }
+
+    fn fault(&self, kind: FaultKind) -> SymbolicError {
+        SymbolicError::Fault { ip: self.sp, kind }
+    }
+
+    fn checked(&self, value: Option<i64>) -> Result<i64, SymbolicError> {
+        value.ok_or_else(|| self.fault(FaultKind::Overflow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+
+    const DAY_02: &str = include_str!("../../day_02/input.txt");
+
+    fn noun_verb(noun: i64, verb: i64) -> Vec<(String, i64)> {
+        vec![("noun".to_string(), noun), ("verb".to_string(), verb)]
+    }
+
+    #[test]
+    fn solves_day_02() {
+        let program = Program::parse(DAY_02).unwrap();
+        let mut computer = SymbolicComputer::new(&program);
+        computer.symbol(1, "noun");
+        computer.symbol(2, "verb");
+        assert_eq!(computer.run(), Ok(HaltReason::Halted));
+        let result = computer.get(0).linear().unwrap();
+        assert_eq!(result.to_string(), "1114711 + 216000*noun + verb");
+
+        let ranges = [("noun", 0..100), ("verb", 0..100)];
+        assert_eq!(result.solve(19690720, &ranges), Some(noun_verb(86, 9)));
+        // Part 1 is noun 12 and verb 2
+        assert_eq!(
+            computer.get(0).eval(&[("noun", 12), ("verb", 2)]),
+            Some(3706713)
+        );
+        assert_eq!(result.solve(19690720 + 100, &ranges), None);
+    }
+
+    #[test]
+    fn overflow_faults_or_has_no_solution() {
+        let mut computer = SymbolicComputer::new(&[1101, i64::MAX, 1, 0, 99]);
+        assert_eq!(
+            computer.run(),
+            Err(SymbolicError::Fault {
+                ip: 0,
+                kind: FaultKind::Overflow
+            })
+        );
+        let mut computer = SymbolicComputer::new(&[109, i64::MIN, 209, -1, 99]);
+        assert_eq!(
+            computer.run(),
+            Err(SymbolicError::Fault {
+                ip: 2,
+                kind: FaultKind::Overflow
+            })
+        );
+
+        let big = Expr::var("x") * Expr::Const(i64::MAX) + Expr::Const(i64::MAX);
+        assert_eq!(big.eval(&[("x", 1)]), None);
+        assert_eq!(big.eval(&[("x", -1)]), Some(0));
+        let unfolded = Expr::Const(i64::MAX) + Expr::Const(1);
+        assert_eq!(unfolded.eval(&[]), None);
+        assert_eq!(unfolded.linear(), None);
+
+        let linear = (Expr::var("x") * Expr::Const(i64::MIN)).linear().unwrap();
+        assert_eq!(
+            linear.solve(i64::MIN, &[("x", -5..5)]),
+            Some(vec![("x".to_string(), 1)])
+        );
+        assert_eq!(linear.solve(0, &[("x", 1..5)]), None);
+        let linear = (Expr::var("x") * Expr::Const(-1)).linear().unwrap();
+        assert_eq!(linear.solve(i64::MIN, &[]), None);
+        let linear = (Expr::var("x") + Expr::Const(-2)).linear().unwrap();
+        assert_eq!(linear.solve(i64::MAX, &[]), None);
+    }
+
+    #[test]
+    fn reads_are_not_folded() {
+        let read = Expr::Read(Arc::new(Expr::var("a")));
+        assert_eq!(
+            read.clone().equals(read.clone()),
+            Expr::Eq(Arc::new(read.clone()), Arc::new(read.clone()))
+        );
+        assert!(read.clone().less_than(read.clone()).as_const().is_none());
+        assert_eq!(Expr::var("a").equals(Expr::var("a")), Expr::Const(1));
+
+        // [p] is read, cell 0 written, then [p] read again. If p is 0 they differ.
+        let mut computer = SymbolicComputer::new(&[
+            1001, 0, 0, 20, // ADD [p], #0, [20]
+            1101, 7, 0, 0, //  ADD #7, #0, [0]
+            8, 0, 20, 21, //   EQ [p], [20], [21]
+            99,
+        ]);
+        computer.symbol(1, "p");
+        computer.symbol(9, "p");
+        assert_eq!(computer.run(), Ok(HaltReason::Halted));
+        assert!(computer.get(21).as_const().is_none());
+    }
+
+    #[test]
+    fn writes_near_the_limit_stay_sparse() {
+        let last = DEFAULT_MEMORY_LIMIT as i64 - 1;
+        // ADD #5, #0, [last], then OUT [last] and OUT [last - 1]
+        let mut computer = SymbolicComputer::new(&[1101, 5, 0, last, 4, last, 4, last - 1, 99]);
+        computer.symbol(last as usize - 2, "x");
+        assert_eq!(computer.run(), Ok(HaltReason::Halted));
+        assert_eq!(computer.output, [Expr::Const(5), Expr::Const(0)]);
+        assert_eq!(computer.get(last as usize - 2), Expr::var("x"));
+        assert_eq!((computer.memory.len(), computer.beyond.len()), (9, 2));
+    }
+}
@@ -0,0 +1,331 @@
+//! Observers for watching a `SuperComputer` run, pass them to `run_with`,
+//! `run_until_event_with` or `step_with`.
+
+use crate::{Observer, OpCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Writes a line per executed instruction: its address, mnemonic, input parameter
+/// values, and the address and value written if any.
+///
+/// ```text
+/// 0011 MUL 3 1 -> [1000] = 3
+/// 0048 OUT 2752191671
+/// ```
+pub struct Tracer<W: Write> {
+    writer: W,
+    write: Option<(usize, i64)>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(writer: W) -> Tracer<W> {
+        Tracer {
+            writer,
+            write: None,
+            error: None,
+        }
+    }
+
+    // The writer back, or the first error writing to it
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.writer),
+        }
+    }
+
+    fn line(&mut self, ip: usize, mnemonic: &str, inputs: &[i64]) -> io::Result<()> {
+        write!(self.writer, "{:04} {}", ip, mnemonic)?;
+        for input in inputs {
+            write!(self.writer, " {}", input)?;
+        }
+        if let Some((address, value)) = self.write.take() {
+            write!(self.writer, " -> [{}] = {}", address, value)?;
+        }
+        writeln!(self.writer)
+    }
+}
+
+impl<W: Write> Observer for Tracer<W> {
+    fn on_write(&mut self, address: usize, _old: i64, new: i64) {
+        self.write = Some((address, new));
+    }
+
+    fn on_instruction(&mut self, ip: usize, op_code: OpCode, inputs: &[i64]) {
+        self.on_custom(ip, op_code.code(), op_code.mnemonic(), inputs);
+    }
+
+    fn on_custom(&mut self, ip: usize, _code: i64, mnemonic: &str, params: &[i64]) {
+        if self.error.is_none() {
+            if let Err(err) = self.line(ip, mnemonic, params) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Counts executed instructions, by op code and by address
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    pub instructions: u64,
+    // Standard op codes only, custom instructions are in the other counts
+    pub op_codes: HashMap<OpCode, u64>,
+    // Times the instruction at each address ran
+    pub addresses: Vec<u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    // The n most executed addresses, most executed first
+    pub fn hottest(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hot: Vec<(usize, u64)> = self
+            .addresses
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(n);
+        hot
+    }
+
+    // Start counting a new run from zero
+    pub fn clear(&mut self) {
+        *self = Profiler::default();
+    }
+}
+
+impl Observer for Profiler {
+    fn on_instruction(&mut self, ip: usize, op_code: OpCode, inputs: &[i64]) {
+        *self.op_codes.entry(op_code).or_insert(0) += 1;
+        self.on_custom(ip, op_code.code(), op_code.mnemonic(), inputs);
+    }
+
+    fn on_custom(&mut self, ip: usize, _code: i64, _mnemonic: &str, _params: &[i64]) {
+        self.instructions += 1;
+        if ip >= self.addresses.len() {
+            self.addresses.resize(ip + 1, 0);
+        }
+        self.addresses[ip] += 1;
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "instructions: {}", self.instructions)?;
+        let mut op_codes: Vec<(&OpCode, &u64)> = self.op_codes.iter().collect();
+        op_codes.sort_by(|a, b| b.1.cmp(a.1));
+        for (op_code, count) in op_codes {
+            let percent = *count as f64 * 100.0 / self.instructions as f64;
+            writeln!(
+                f,
+                "  {:<4}{:>12} {:>6.2}%",
+                op_code.mnemonic(),
+                count,
+                percent
+            )?;
+        }
+        writeln!(f, "hottest addresses:")?;
+        for (address, count) in self.hottest(10) {
+            writeln!(f, "  {:04}{:>12}", address, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// A write to a cell that is part of an executed instruction, found by `CodeWatcher`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CodeWrite {
+    // The instruction doing the writing
+    pub ip: usize,
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+    // Whether the cell had already been executed, otherwise it was executed later
+    pub executed_before: bool,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04} wrote {} over {} at {:04}, {}",
+            self.ip,
+            self.new,
+            self.old,
+            self.address,
+            if self.executed_before {
+                "already executed"
+            } else {
+                "executed later"
+            }
+        )
+    }
+}
+
+/// Finds self-modifying code: writes to cells of instructions that have run, or that
+/// run afterwards. A cell counts as executed when it is the op code or a parameter of
+/// an instruction that ran. When a cell is written more than once before it runs, only
+/// the last write is reported, the earlier values never ran.
+#[derive(Debug, Clone, Default)]
+pub struct CodeWatcher {
+    pub writes: Vec<CodeWrite>,
+    executed: Vec<bool>,
+    // Writes to cells that haven't run yet, by address
+    pending: HashMap<usize, CodeWrite>,
+    // From on_write, waiting for on_instruction to say who wrote it
+    write: Option<(usize, i64, i64)>,
+}
+
+impl CodeWatcher {
+    pub fn new() -> CodeWatcher {
+        CodeWatcher::default()
+    }
+
+    pub fn is_self_modifying(&self) -> bool {
+        !self.writes.is_empty()
+    }
+
+    // Whether the cell at address has been executed
+    pub fn executed(&self, address: usize) -> bool {
+        self.executed.get(address).copied().unwrap_or(false)
+    }
+
+    fn ran(&mut self, ip: usize, len: usize) {
+        if ip + len > self.executed.len() {
+            self.executed.resize(ip + len, false);
+        }
+        for address in ip..ip + len {
+            if !self.executed[address] {
+                self.executed[address] = true;
+                self.writes.extend(self.pending.remove(&address));
+            }
+        }
+        // The instruction has already read its own cells when it writes one of them
+        if let Some((address, old, new)) = self.write.take() {
+            let write = CodeWrite {
+                ip,
+                address,
+                old,
+                new,
+                executed_before: self.executed(address),
+            };
+            if write.executed_before {
+                self.writes.push(write);
+            } else {
+                self.pending.insert(address, write);
+            }
+        }
+    }
+}
+
+impl Observer for CodeWatcher {
+    fn on_write(&mut self, address: usize, old: i64, new: i64) {
+        self.write = Some((address, old, new));
+    }
+
+    fn on_instruction(&mut self, ip: usize, op_code: OpCode, _inputs: &[i64]) {
+        self.ran(ip, op_code.num_params() + 1);
+    }
+
+    fn on_custom(&mut self, ip: usize, _code: i64, _mnemonic: &str, params: &[i64]) {
+        self.ran(ip, params.len() + 1);
+    }
+}
+
+impl fmt::Display for CodeWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "writes to code: {}", self.writes.len())?;
+        for write in &self.writes {
+            writeln!(f, "  {}", write)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Action, InstructionSet, OpDef, Program, SuperComputer};
+
+    #[test]
+    fn profiler_counts_what_the_computer_counts() {
+        // Count down from 3 with a custom DEC, then run again once halted
+        let mut isa = InstructionSet::standard();
+        isa.register(
+            50,
+            OpDef::new("DEC", 1, &[0], |context| {
+                context.write(0, context.memory().get(context.param(0) as usize) - 1);
+                Ok(Action::Next)
+            }),
+        );
+        let program = vec![4, 9, 50, 9, 1005, 9, 0, 99, 0, 3];
+        let mut computer = SuperComputer::standalone("countdown".to_string(), program);
+        computer.set_instruction_set(isa);
+        let mut profiler = Profiler::default();
+        computer.run_with(&mut profiler).unwrap();
+        computer.run_with(&mut profiler).unwrap();
+        assert_eq!(computer.output, [3, 2, 1]);
+        assert_eq!(profiler.instructions, computer.instructions());
+        assert_eq!(profiler.op_codes.get(&OpCode::Halt), None);
+    }
+
+    #[test]
+    fn code_watcher_reports_both_kinds_of_write() {
+        let program = assemble(
+            "
+            ADD  #1, #0, [t+1]
+            ADD  #2, #0, [t+1]
+    t:      OUT  #0
+            ADD  #3, #0, [0]
+            HLT
+    ",
+        )
+        .unwrap();
+        let mut computer = SuperComputer::standalone("patch".to_string(), program);
+        let mut watcher = CodeWatcher::new();
+        computer.run_with(&mut watcher).unwrap();
+        assert_eq!(computer.output, [2]);
+        let write = |ip, address, old, new, executed_before| CodeWrite {
+            ip,
+            address,
+            old,
+            new,
+            executed_before,
+        };
+        // The OUT's parameter only counts once the OUT runs, and only as the last
+        // value written to it. The write over the first ADD comes after it ran.
+        assert_eq!(
+            watcher.writes,
+            [write(4, 9, 1, 2, false), write(10, 0, 1101, 3, true)]
+        );
+    }
+
+    #[test]
+    fn code_watcher_finds_the_day_5_patch() {
+        // Day 5 adds its input to the op code at 6, turning 1100 into a 1101 ADD
+        let program = Program::parse(include_str!("../../day_05/input.txt")).unwrap();
+        let mut computer = SuperComputer::standalone("day 5".to_string(), program);
+        computer.provide_input(1);
+        let mut watcher = CodeWatcher::new();
+        computer.run_with(&mut watcher).unwrap();
+        assert!(watcher.is_self_modifying());
+        assert_eq!(
+            watcher.writes[0],
+            CodeWrite {
+                ip: 2,
+                address: 6,
+                old: 1100,
+                new: 1101,
+                executed_before: false,
+            }
+        );
+        assert!(watcher.executed(6));
+    }
+}